@@ -0,0 +1,32 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+use nes_emulator_rs::bus::Bus;
+use nes_emulator_rs::cart::Cart;
+use nes_emulator_rs::cpu::CPU;
+
+const NESTEST_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/roms/tests/nestest.nes");
+
+// nestest's automated mode starting at $C000 runs through every official and unofficial opcode
+fn nestest(c: &mut Criterion) {
+    c.bench_function("nestest 8000 instructions", |b| {
+        b.iter_batched(
+            || {
+                let bus = Bus::new(Cart::new(NESTEST_PATH));
+                let mut cpu = CPU::new(bus);
+                cpu.reset();
+                cpu.regs.pc = 0xC000;
+                cpu
+            },
+            |mut cpu| {
+                for _ in 0..8000 {
+                    cpu.step();
+                }
+                cpu
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(benches, nestest);
+criterion_main!(benches);
@@ -0,0 +1,18 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use nes_emulator_rs::cart::Cart;
+use nes_emulator_rs::nes::Nes;
+
+const NESTEST_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/roms/tests/nestest.nes");
+
+fn snapshot(c: &mut Criterion) {
+    let mut nes = Nes::new(Cart::new(NESTEST_PATH));
+    nes.run_frame();
+    let mut buf = Vec::new();
+
+    c.bench_function("snapshot", |b| b.iter(|| nes.snapshot_into(&mut buf)));
+    c.bench_function("restore", |b| b.iter(|| nes.restore(&buf).unwrap()));
+}
+
+criterion_group!(benches, snapshot);
+criterion_main!(benches);
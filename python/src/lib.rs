@@ -0,0 +1,225 @@
+/*
+    Python bindings for the headless emulator core, build with `maturin develop` from this directory.
+
+        import nes_emulator
+        nes = nes_emulator.Nes("game.nes")
+        frame = nes.framebuffer()  # (240, 256, 3) uint8 view, updated in place by run_frame()
+        ram = nes.ram()            # (2048,) uint8 view
+        nes.set_buttons(0, 0b0000_1000)
+        nes.run_frame()
+*/
+use numpy::ndarray::{s, ArrayView1, ArrayView3};
+use numpy::{PyArray1, PyArray3, PyArrayDyn, PyArrayMethods, PyReadonlyArray1};
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+
+use nes_emulator_rs::cart::{Cart, CartError};
+use nes_emulator_rs::env::{EnvConfig, NesEnv, ObservationType};
+use nes_emulator_rs::nes::Nes;
+use nes_emulator_rs::rendering::Frame;
+use nes_emulator_rs::vec_nes::VecNes;
+use nes_emulator_rs::{WINDOW_HEIGHT, WINDOW_WIDTH};
+
+#[pyclass(name = "Nes")]
+struct PyNes {
+    nes: Nes,
+}
+
+#[pymethods]
+impl PyNes {
+    #[new]
+    #[pyo3(signature = (rom_path, seed=0))]
+    fn new(rom_path: &str, seed: u64) -> PyResult<Self> {
+        Ok(PyNes {
+            nes: Nes::with_seed(load_cart(rom_path)?, seed),
+        })
+    }
+
+    fn load_rom(&mut self, rom_path: &str) -> PyResult<()> {
+        self.nes.load_rom(rom_path).map_err(|err| cart_error(rom_path, err))
+    }
+
+    fn step_instruction(&mut self) {
+        self.nes.step_instruction();
+    }
+
+    fn run_frame(&mut self) {
+        self.nes.run_frame();
+    }
+
+    fn set_buttons(&mut self, player: usize, mask: u8) {
+        self.nes.set_buttons(player, mask);
+    }
+
+    fn reset(&mut self) {
+        self.nes.reset();
+    }
+
+    fn power_cycle(&mut self) {
+        self.nes.power_cycle();
+    }
+
+    // Read-only (240, 256, 3) view of the frame buffer, it tracks the emulator without copying
+    fn framebuffer<'py>(this: Bound<'py, Self>) -> Bound<'py, PyArray3<u8>> {
+        let nes = this.borrow();
+        let frame = ArrayView3::from_shape((WINDOW_HEIGHT, Frame::WIDTH, 3), &nes.nes.framebuffer().data[..WINDOW_HEIGHT * Frame::WIDTH * 3])
+            .unwrap()
+            .slice_move(s![.., ..WINDOW_WIDTH, ..]);
+
+        // SAFETY: The frame buffer is owned by this object and only ever cleared in place, never reallocated
+        let array = unsafe { PyArray3::borrow_from_array(&frame, this.clone().into_any()) };
+        array.readwrite().make_nonwriteable();
+        array
+    }
+
+    // Read-only view of the 2 KiB of work RAM
+    fn ram<'py>(this: Bound<'py, Self>) -> Bound<'py, PyArray1<u8>> {
+        let nes = this.borrow();
+        let ram = ArrayView1::from(&nes.nes.ram()[..]);
+
+        // SAFETY: The RAM is an array stored inline in the machine owned by this object
+        let array = unsafe { PyArray1::borrow_from_array(&ram, this.clone().into_any()) };
+        array.readwrite().make_nonwriteable();
+        array
+    }
+
+    fn save_state<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.nes.snapshot())
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> PyResult<()> {
+        self.nes.restore(state).map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+}
+
+// Files that can't be read are IOErrors, files that aren't valid ROMs are ValueErrors
+fn cart_error(rom_path: &str, err: CartError) -> PyErr {
+    let message = format!("Unable to load {}: {}", rom_path, err);
+    match err {
+        CartError::Io(_) => PyIOError::new_err(message),
+        _ => PyValueError::new_err(message),
+    }
+}
+
+fn load_cart(rom_path: &str) -> PyResult<Cart> {
+    Cart::load(rom_path).map_err(|err| cart_error(rom_path, err))
+}
+
+fn load_config(config_path: Option<&str>, config: Option<&str>) -> PyResult<EnvConfig> {
+    let config = match (config_path, config) {
+        (Some(path), None) => EnvConfig::load(path),
+        (None, Some(config)) => EnvConfig::parse(config),
+        (None, None) => EnvConfig::parse(""),
+        (Some(_), Some(_)) => return Err(PyValueError::new_err("Pass either config_path or config, not both")),
+    };
+    config.map_err(|err| PyValueError::new_err(err.to_string()))
+}
+
+fn observation_shape(config: &EnvConfig) -> Vec<usize> {
+    match config.observation {
+        ObservationType::Rgb => vec![WINDOW_HEIGHT, WINDOW_WIDTH, 3],
+        ObservationType::Greyscale => vec![WINDOW_HEIGHT / config.downsample, WINDOW_WIDTH / config.downsample],
+        ObservationType::Ram => vec![2048],
+    }
+}
+
+fn to_array<'py>(py: Python<'py>, data: &[u8], shape: Vec<usize>) -> PyResult<Bound<'py, PyArrayDyn<u8>>> {
+    PyArray1::from_slice(py, data).reshape(shape)
+}
+
+// Gym-style environment, observations are copies so they stay valid across steps
+#[pyclass(name = "Env")]
+struct PyEnv {
+    env: NesEnv,
+    shape: Vec<usize>,
+}
+
+#[pymethods]
+impl PyEnv {
+    #[new]
+    #[pyo3(signature = (rom_path, config_path=None, config=None))]
+    fn new(rom_path: &str, config_path: Option<&str>, config: Option<&str>) -> PyResult<Self> {
+        let config = load_config(config_path, config)?;
+        let shape = observation_shape(&config);
+        Ok(PyEnv {
+            env: NesEnv::new(load_cart(rom_path)?, config),
+            shape,
+        })
+    }
+
+    fn reset<'py>(&mut self, py: Python<'py>) -> PyResult<Bound<'py, PyArrayDyn<u8>>> {
+        let observation = self.env.reset();
+        to_array(py, &observation, self.shape.clone())
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn step<'py>(&mut self, py: Python<'py>, action: u8) -> PyResult<(Bound<'py, PyArrayDyn<u8>>, f64, bool, Bound<'py, PyDict>)> {
+        let (observation, reward, done, info) = self.env.step(action);
+
+        let py_info = PyDict::new(py);
+        py_info.set_item("frame", info.frame)?;
+        for (name, value) in info.values {
+            py_info.set_item(name, value)?;
+        }
+
+        Ok((to_array(py, &observation, self.shape.clone())?, reward, done, py_info))
+    }
+}
+
+#[pyclass(name = "VecEnv")]
+struct PyVecEnv {
+    envs: VecNes,
+    shape: Vec<usize>,
+}
+
+#[pymethods]
+impl PyVecEnv {
+    #[new]
+    #[pyo3(signature = (rom_path, num_envs, config_path=None, config=None))]
+    fn new(rom_path: &str, num_envs: usize, config_path: Option<&str>, config: Option<&str>) -> PyResult<Self> {
+        // Checked here, VecNes panics on them
+        if num_envs == 0 {
+            return Err(PyValueError::new_err("num_envs must be at least 1"));
+        }
+        let config = load_config(config_path, config)?;
+        let mut shape = observation_shape(&config);
+        shape.insert(0, num_envs);
+        Ok(PyVecEnv {
+            envs: VecNes::new(load_cart(rom_path)?, config, num_envs),
+            shape,
+        })
+    }
+
+    fn reset<'py>(&mut self, py: Python<'py>) -> PyResult<Bound<'py, PyArrayDyn<u8>>> {
+        let observations = self.envs.reset();
+        to_array(py, observations, self.shape.clone())
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn step<'py>(&mut self, py: Python<'py>, actions: PyReadonlyArray1<'py, u8>) -> PyResult<(Bound<'py, PyArrayDyn<u8>>, Bound<'py, PyArray1<f64>>, Bound<'py, PyArray1<bool>>)> {
+        let actions = actions.as_slice()?.to_vec();
+        if actions.len() != self.envs.num_envs() {
+            return Err(PyValueError::new_err(format!("Expected {} actions, one per environment, got {}", self.envs.num_envs(), actions.len())));
+        }
+        let shape = self.shape.clone();
+        let envs = &mut self.envs;
+
+        // The emulators don't touch Python objects, let other Python threads run meanwhile
+        let (observations, rewards, dones) = py.detach(|| envs.step(&actions));
+
+        Ok((
+            to_array(py, observations, shape)?,
+            PyArray1::from_slice(py, rewards),
+            PyArray1::from_slice(py, dones),
+        ))
+    }
+}
+
+#[pymodule]
+fn nes_emulator(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyNes>()?;
+    m.add_class::<PyEnv>()?;
+    m.add_class::<PyVecEnv>()?;
+    Ok(())
+}
@@ -0,0 +1,100 @@
+/*
+    ROMs stored compressed. The format is told by the file's magic bytes rather than its extension: a zip
+    archive gives its first .nes file, or the one asked for by name, a gzip file holds a single ROM, and
+    anything else is taken to be the ROM itself.
+*/
+use std::io::{self, Cursor, Read};
+
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+use crate::cart::CartError;
+
+const ZIP_MAGIC: [u8; 4] = *b"PK\x03\x04";
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+// Largest unpacked ROM accepted, far beyond any real cartridge, so a crafted archive can't exhaust memory
+const MAX_ROM_SIZE: usize = 64 * 1024 * 1024;
+
+// The ROM in the data read from a file, `entry` picks a file in a zip archive by its path or name
+pub fn unpack(data: Vec<u8>, entry: Option<&str>) -> Result<Vec<u8>, CartError> {
+    if data.starts_with(&ZIP_MAGIC) {
+        unzip(data, entry)
+    } else if data.starts_with(&GZIP_MAGIC) {
+        read_capped(GzDecoder::new(data.as_slice())).map_err(|err| CartError::Archive(err.to_string()))
+    } else {
+        Ok(data)
+    }
+}
+
+// Reads everything, failing once more than MAX_ROM_SIZE bytes come out rather than trusting a declared size
+fn read_capped(reader: impl Read) -> io::Result<Vec<u8>> {
+    let mut rom = Vec::new();
+    reader.take(MAX_ROM_SIZE as u64 + 1).read_to_end(&mut rom)?;
+    if rom.len() > MAX_ROM_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Unpacks to more than {} bytes", MAX_ROM_SIZE)));
+    }
+    Ok(rom)
+}
+
+fn unzip(data: Vec<u8>, entry: Option<&str>) -> Result<Vec<u8>, CartError> {
+    let archive_error = |err: zip::result::ZipError| CartError::Archive(err.to_string());
+    let mut archive = ZipArchive::new(Cursor::new(data)).map_err(archive_error)?;
+
+    let matches = |name: &str| match entry {
+        Some(entry) => name == entry || name.rsplit('/').next() == Some(entry),
+        None => name.to_ascii_lowercase().ends_with(".nes"),
+    };
+    let index = (0..archive.len())
+        .find(|&index| archive.name_for_index(index).is_some_and(matches))
+        .ok_or_else(|| CartError::NoRomInArchive(entry.map(str::to_string)))?;
+
+    let file = archive.by_index(index).map_err(archive_error)?;
+    read_capped(file).map_err(|err| CartError::Archive(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    use crate::test_util::NESTEST_PATH;
+
+    #[test]
+    fn test_unpack() {
+        let rom = fs::read(NESTEST_PATH).unwrap();
+        assert_eq!(unpack(rom.clone(), None).unwrap(), rom);
+
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(&rom).unwrap();
+        assert_eq!(unpack(gzip.finish().unwrap(), None).unwrap(), rom);
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in [("readme.txt", b"readme".as_slice()), ("roms/nestest.NES", &rom), ("roms/other.nes", &rom[..16])] {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        let zip = zip.finish().unwrap().into_inner();
+        assert_eq!(unpack(zip.clone(), None).unwrap(), rom);
+        assert_eq!(unpack(zip.clone(), Some("other.nes")).unwrap(), &rom[..16]);
+        assert_eq!(unpack(zip.clone(), Some("readme.txt")).unwrap(), b"readme");
+        assert!(matches!(unpack(zip, Some("missing.nes")), Err(CartError::NoRomInArchive(Some(_)))));
+    }
+
+    #[test]
+    fn test_size_cap() {
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(&vec![0; MAX_ROM_SIZE + 1]).unwrap();
+        assert!(matches!(unpack(gzip.finish().unwrap(), None), Err(CartError::Archive(_))));
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("big.nes", SimpleFileOptions::default()).unwrap();
+        zip.write_all(&vec![0; MAX_ROM_SIZE + 1]).unwrap();
+        assert!(matches!(unpack(zip.finish().unwrap().into_inner(), None), Err(CartError::Archive(_))));
+    }
+}
@@ -0,0 +1,344 @@
+/*
+    Keyboard and gamepad bindings for the SDL frontend, kept in bindings.toml in the platform config directory.
+    The file is written with the defaults the first time the emulator runs. Keys use SDL's key names ("A",
+    "Space", "Left Shift") and gamepad buttons use SDL's button names ("a", "back", "dpup").
+
+    Every binding has a name like player1.a or hotkeys.quit, which is what --bind and the in-app binding
+    prompt refer to it by. An empty key leaves the binding unused.
+
+    Input macros are kept in the same file, each with the key that plays it.
+*/
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use nes_emulator_rs::input_macro::{InputMacro, MacroStep};
+use nes_emulator_rs::joypad::Inputs;
+use sdl2::controller::Button;
+use nes_emulator_rs::nes::PLAYERS;
+use sdl2::keyboard::Keycode;
+use serde_derive::{Deserialize, Serialize};
+
+// Name, button and whether it is the turbo version of the button
+const BUTTONS: [(&str, Inputs, bool); 10] = [
+    ("a", Inputs::A, false),
+    ("b", Inputs::B, false),
+    ("select", Inputs::Select, false),
+    ("start", Inputs::Start, false),
+    ("up", Inputs::Up, false),
+    ("down", Inputs::Down, false),
+    ("left", Inputs::Left, false),
+    ("right", Inputs::Right, false),
+    ("turbo_a", Inputs::A, true),
+    ("turbo_b", Inputs::B, true),
+];
+
+#[derive(Clone, Copy)]
+pub enum Action {
+    Button(usize, Inputs),
+    Turbo(usize, Inputs),
+    PowerPad(u8),
+    // Index into Bindings::macros
+    Macro(usize),
+    RecordMacro,
+    Slot(u8),
+    Quit,
+    SaveState,
+    LoadState,
+    Rewind,
+    Bind,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ControllerKeys {
+    pub a: String,
+    pub b: String,
+    pub select: String,
+    pub start: String,
+    pub up: String,
+    pub down: String,
+    pub left: String,
+    pub right: String,
+    #[serde(default)]
+    pub turbo_a: String,
+    #[serde(default)]
+    pub turbo_b: String,
+}
+
+impl ControllerKeys {
+    fn new(keys: [&str; 10]) -> Self {
+        let [a, b, select, start, up, down, left, right, turbo_a, turbo_b] = keys.map(String::from);
+        ControllerKeys { a, b, select, start, up, down, left, right, turbo_a, turbo_b }
+    }
+
+    // In the same order as BUTTONS
+    fn keys(&self) -> [&String; 10] {
+        [
+            &self.a, &self.b, &self.select, &self.start, &self.up,
+            &self.down, &self.left, &self.right, &self.turbo_a, &self.turbo_b,
+        ]
+    }
+
+    fn keys_mut(&mut self) -> [&mut String; 10] {
+        [
+            &mut self.a, &mut self.b, &mut self.select, &mut self.start, &mut self.up,
+            &mut self.down, &mut self.left, &mut self.right, &mut self.turbo_a, &mut self.turbo_b,
+        ]
+    }
+}
+
+// What a gamepad button does
+#[derive(Clone, Copy)]
+pub struct PadButton {
+    pub input: Inputs,
+    pub turbo: bool,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Hotkeys {
+    pub quit: String,
+    pub save_state: String,
+    pub load_state: String,
+    pub rewind: String,
+    // Starts the press a key to bind prompt
+    pub bind: String,
+    // Starts recording a macro from player 1, pressing it again stops
+    #[serde(default)]
+    pub record_macro: String,
+    // Select save slots 0-9
+    pub slots: [String; 10],
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct MacroBinding {
+    pub key: String,
+    // 1-4
+    pub player: usize,
+    pub steps: Vec<MacroStep>,
+}
+
+impl MacroBinding {
+    pub fn input_macro(&self) -> InputMacro {
+        InputMacro { steps: self.steps.clone() }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Bindings {
+    // Turbo presses per second
+    pub turbo_rate: usize,
+    pub player1: ControllerKeys,
+    pub player2: ControllerKeys,
+    // Gamepad buttons, shared by every pad
+    pub gamepad: ControllerKeys,
+    pub hotkeys: Hotkeys,
+    // Power Pad buttons 1-12
+    pub power_pad: [String; 12],
+    pub macros: Vec<MacroBinding>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings {
+            turbo_rate: 10,
+            player1: ControllerKeys::new(["A", "S", "Space", "Return", "Up", "Down", "Left", "Right", "Q", "W"]),
+            player2: ControllerKeys::new(["O", "U", "Y", "P", "I", "K", "J", "L", "[", "]"]),
+            // By position rather than label, the bottom face button is B and the right one is A like on a NES pad
+            gamepad: ControllerKeys::new(["b", "a", "back", "start", "dpup", "dpdown", "dpleft", "dpright", "y", "x"]),
+            hotkeys: Hotkeys {
+                quit: "Escape".to_string(),
+                save_state: "Z".to_string(),
+                load_state: "X".to_string(),
+                rewind: "Backspace".to_string(),
+                bind: "F1".to_string(),
+                record_macro: "F2".to_string(),
+                slots: ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"].map(String::from),
+            },
+            // Laid out in the same 4x3 grid as the mat
+            power_pad: ["T", "Y", "U", "I", "G", "H", "J", "K", "B", "N", "M", ","].map(String::from),
+            macros: Vec::new(),
+        }
+    }
+}
+
+impl Bindings {
+    pub fn default_path() -> PathBuf {
+        match dirs::config_dir() {
+            Some(dir) => dir.join("nes-emulator-rs").join("bindings.toml"),
+            None => PathBuf::from("bindings.toml"),
+        }
+    }
+
+    // Writes the defaults out when there is no file yet, so there is something to edit
+    pub fn load(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            let bindings = Bindings::default();
+            if let Err(err) = bindings.save(path) {
+                eprintln!("Unable to write default bindings to {}: {}", path.display(), err);
+            }
+            return Ok(bindings);
+        }
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        let bindings: Bindings = toml::from_str(&text).map_err(|err| format!("{}: {}", path.display(), err))?;
+        bindings.keymap()?;
+        bindings.button_map()?;
+        if bindings.turbo_rate == 0 {
+            return Err(format!("{}: turbo_rate must be at least 1", path.display()));
+        }
+        if let Some(binding) = bindings.macros.iter().find(|binding| !(1..=PLAYERS).contains(&binding.player)) {
+            return Err(format!("{}: macro on {} is for player {}, expected 1 to {}", path.display(), binding.key, binding.player, PLAYERS));
+        }
+        Ok(bindings)
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string(self).unwrap())
+    }
+
+    // Every keyboard binding, by name, in the order the binding prompt asks for them
+    pub fn names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        for player in ["player1", "player2"] {
+            names.extend(BUTTONS.iter().map(|(button, _, _)| format!("{}.{}", player, button)));
+        }
+        names.extend(["quit", "save_state", "load_state", "rewind", "bind", "record_macro"].map(|hotkey| format!("hotkeys.{}", hotkey)));
+        names.extend((0..10).map(|slot| format!("hotkeys.slot{}", slot)));
+        names.extend((1..=12).map(|button| format!("power_pad.{}", button)));
+        names
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut String> {
+        let (group, binding) = name.split_once('.')?;
+        let controller = match group {
+            "player1" => &mut self.player1,
+            "player2" => &mut self.player2,
+            "gamepad" => &mut self.gamepad,
+            "hotkeys" => {
+                return match binding {
+                    "quit" => Some(&mut self.hotkeys.quit),
+                    "save_state" => Some(&mut self.hotkeys.save_state),
+                    "load_state" => Some(&mut self.hotkeys.load_state),
+                    "rewind" => Some(&mut self.hotkeys.rewind),
+                    "bind" => Some(&mut self.hotkeys.bind),
+                    "record_macro" => Some(&mut self.hotkeys.record_macro),
+                    _ => {
+                        let slot: usize = binding.strip_prefix("slot")?.parse().ok()?;
+                        self.hotkeys.slots.get_mut(slot)
+                    }
+                };
+            }
+            "power_pad" => {
+                let button: usize = binding.parse().ok()?;
+                return self.power_pad.get_mut(button.checked_sub(1)?);
+            }
+            _ => return None,
+        };
+        let index = BUTTONS.iter().position(|(button, _, _)| *button == binding)?;
+        controller.keys_mut().into_iter().nth(index)
+    }
+
+    // Applies a --bind override, given as <name>=<key>
+    pub fn set(&mut self, binding: &str) -> Result<(), String> {
+        let (name, key) = binding.split_once('=').ok_or_else(|| format!("Expected <name>=<key>, got {}", binding))?;
+        let value = self.get_mut(name).ok_or_else(|| format!("Unknown binding {}", name))?;
+        *value = key.to_string();
+        if name.starts_with("gamepad.") {
+            self.button_map()?;
+        } else {
+            self.keymap()?;
+        }
+        Ok(())
+    }
+
+    // A key can do several things, the default Power Pad keys overlap with player 2
+    pub fn keymap(&self) -> Result<HashMap<Keycode, Vec<Action>>, String> {
+        let mut keymap: HashMap<Keycode, Vec<Action>> = HashMap::new();
+        let mut bind = |name: &str, action: Action| -> Result<(), String> {
+            if name.is_empty() {
+                return Ok(());
+            }
+            let keycode = Keycode::from_name(name).ok_or_else(|| format!("Unknown key {:?}", name))?;
+            keymap.entry(keycode).or_default().push(action);
+            Ok(())
+        };
+
+        for (player, keys) in [&self.player1, &self.player2].into_iter().enumerate() {
+            for (key, &(_, input, turbo)) in keys.keys().into_iter().zip(BUTTONS.iter()) {
+                bind(key, if turbo { Action::Turbo(player, input) } else { Action::Button(player, input) })?;
+            }
+        }
+        bind(&self.hotkeys.quit, Action::Quit)?;
+        bind(&self.hotkeys.save_state, Action::SaveState)?;
+        bind(&self.hotkeys.load_state, Action::LoadState)?;
+        bind(&self.hotkeys.rewind, Action::Rewind)?;
+        bind(&self.hotkeys.bind, Action::Bind)?;
+        bind(&self.hotkeys.record_macro, Action::RecordMacro)?;
+        for (slot, key) in self.hotkeys.slots.iter().enumerate() {
+            bind(key, Action::Slot(slot as u8))?;
+        }
+        for (button, key) in self.power_pad.iter().enumerate() {
+            bind(key, Action::PowerPad(button as u8 + 1))?;
+        }
+        for (index, binding) in self.macros.iter().enumerate() {
+            bind(&binding.key, Action::Macro(index))?;
+        }
+        Ok(keymap)
+    }
+
+    pub fn button_map(&self) -> Result<HashMap<Button, PadButton>, String> {
+        let mut button_map = HashMap::new();
+        for (name, &(_, input, turbo)) in self.gamepad.keys().into_iter().zip(BUTTONS.iter()) {
+            if name.is_empty() {
+                continue;
+            }
+            let button = Button::from_string(name).ok_or_else(|| format!("Unknown gamepad button {:?}", name))?;
+            button_map.insert(button, PadButton { input, turbo });
+        }
+        Ok(button_map)
+    }
+}
+
+/*
+    The press a key to bind prompt. It goes through every keyboard binding in turn, the bind hotkey keeps
+    the current key and Escape stops early. The new bindings are saved once it is done.
+*/
+pub struct BindPrompt {
+    names: Vec<String>,
+    next: usize,
+    skip: Keycode,
+}
+
+impl BindPrompt {
+    pub fn new(bindings: &mut Bindings) -> Option<Self> {
+        let skip = Keycode::from_name(&bindings.hotkeys.bind)?;
+        println!("Binding keys, press {} to keep a key and Escape to stop", bindings.hotkeys.bind);
+        let prompt = BindPrompt { names: bindings.names(), next: 0, skip };
+        prompt.ask(bindings);
+        Some(prompt)
+    }
+
+    fn ask(&self, bindings: &mut Bindings) {
+        let name = &self.names[self.next];
+        println!("Press a key for {} (currently {})", name, bindings.get_mut(name).unwrap());
+    }
+
+    // Returns false once every binding has been asked for
+    pub fn key_pressed(&mut self, bindings: &mut Bindings, keycode: Keycode) -> bool {
+        if keycode == Keycode::Escape {
+            return false;
+        }
+        if keycode != self.skip {
+            *bindings.get_mut(&self.names[self.next]).unwrap() = keycode.name();
+        }
+        self.next += 1;
+        if self.next == self.names.len() {
+            return false;
+        }
+        self.ask(bindings);
+        true
+    }
+}
@@ -1,51 +1,124 @@
-use crate::cart::Cart;
+use rand::{Rng, RngCore};
+
+use crate::cart::{Cart, Region};
+use crate::{WINDOW_HEIGHT, WINDOW_WIDTH};
 use crate::ppu::PPU;
+use crate::devices::{Device, DeviceType, PortDevice};
 use crate::joypad::Joypad;
-use crate::state::State;
-
-pub enum GameloopAction {
-    NoAction,
-    SaveState,
-    LoadState
-}
+use crate::rendering::{Frame, SYSTEM_PALLETE, render};
+use crate::registers::Registers;
+use crate::state::{State, StateRef};
 
-pub(crate) trait Memory {
+pub trait Memory {
     fn read(&mut self, addr: u16) -> u8;
 
     fn write(&mut self, addr: u16, value: u8);
 
-    fn read_16(&mut self, pos: u16) -> u16;
-  
-    fn write_16(&mut self, pos: u16, value: u16);
+    /*
+    NES CPU uses Little-Endian addressing rather than Big-Endian.
+    That means that the 8 least significant bits of an address will be stored before the 8 most significant bits.
+    */
+    fn read_16(&mut self, addr: u16) -> u16 {
+        let lo = self.read(addr) as u16;
+        let hi = self.read(addr.wrapping_add(1)) as u16;
+        (hi << 8) | lo
+    }
+
+    fn write_16(&mut self, addr: u16, value: u16) {
+        let hi = (value >> 8) as u8;
+        let lo = (value & 0xff) as u8;
+        self.write(addr, lo);
+        self.write(addr.wrapping_add(1), hi);
+    }
+
+    // Advances everything clocked alongside the CPU, called with the cycles each instruction took
+    fn tick(&mut self, _cycles: u8) {}
+
+    fn poll_nmi_status(&mut self) -> Option<u8> {
+        None
+    }
+}
+
+// Scanlines the Zapper's sensor keeps seeing light for after the beam has passed the spot it is aimed at
+const ZAPPER_LIGHT_SCANLINES: usize = 20;
+// Sum of the red, green and blue levels the Zapper needs to see to register light
+const ZAPPER_LIGHT_THRESHOLD: u32 = 0x180;
+
+// The picture as it stands mid-frame, rendered at most once a frame and only when a Zapper needs it
+#[derive(Default)]
+struct LightSensor {
+    frame: Option<Frame>,
+    current: bool,
+}
+
+impl LightSensor {
+    // Light is only seen while the beam is drawing a bright pixel at, or just before, the spot aimed at
+    fn sees_light(&mut self, ppu: &PPU, x: usize, y: usize) -> bool {
+        let scanline = ppu.scanline() as usize;
+        if x >= WINDOW_WIDTH || y >= WINDOW_HEIGHT || scanline < y || scanline >= y + ZAPPER_LIGHT_SCANLINES {
+            return false;
+        }
+
+        let frame = self.frame.get_or_insert_with(Frame::new);
+        if !self.current {
+            render(ppu, &SYSTEM_PALLETE, frame);
+            self.current = true;
+        }
+        let pixel = (y * Frame::WIDTH + x) * 3;
+        frame.data[pixel..pixel + 3].iter().map(|&level| level as u32).sum::<u32>() >= ZAPPER_LIGHT_THRESHOLD
+    }
 }
 
-pub struct Bus<'call> {
+pub struct Bus {
     vram: [u8; 2048],
+    prg_ram: [u8; 0x2000],
     prg_rom: Vec<u8>,
     ppu: PPU,
-    joypad: Joypad,
+    ports: [Device; 2],
+    light_sensor: LightSensor,
     cycles: usize,
-
-    gameloop_callback: Box<dyn FnMut(&PPU, &mut Joypad) -> GameloopAction + 'call>,
+    frame_complete: bool,
 }
  
-impl Bus<'_>{
-    pub(crate) fn new<'call, F>(cart: Cart, gameloop_callback: F) -> Bus<'call> 
-    where
-        F: FnMut(&PPU, &mut Joypad) -> GameloopAction + 'call,
-    {
-        let ppu = PPU::new(cart.chr_rom, cart.rom_header.screen_mirroring);
+impl Bus {
+    pub fn new(cart: Cart) -> Self {
+        let mut ppu = PPU::new(cart.chr_rom, cart.rom_header.screen_mirroring);
+        ppu.set_region(cart.rom_header.region);
+        // NES 2.0 can describe PRG ROM that isn't a whole number of 16 KiB banks, it repeats to fill the last one
+        let prg_rom_size = cart.prg_rom.len().next_multiple_of(0x4000);
+        let prg_rom = cart.prg_rom.iter().copied().cycle().take(prg_rom_size).collect();
 
         Bus {
             vram: [0; 2048],
-            prg_rom: cart.prg_rom,
+            prg_ram: [0; 0x2000],
+            prg_rom,
             ppu,
             cycles: 0,
-            gameloop_callback: Box::from(gameloop_callback),
-            joypad: Joypad::new(),            
+            frame_complete: false,
+            ports: [Device::new(DeviceType::Controller, 0), Device::new(DeviceType::Controller, 1)],
+            light_sensor: LightSensor::default(),
         }
     }
 
+    // Work RAM powers up holding leftover values rather than zeroes, and the PPU starts somewhere in its frame
+    pub(crate) fn power_on<R: RngCore>(&mut self, rng: &mut R) {
+        rng.fill_bytes(&mut self.vram);
+        self.ppu.set_position(rng.gen_range(0, self.ppu.region().scanlines()), rng.gen_range(0, 341));
+    }
+
+    // FCEUX's fixed power-on state: work RAM alternates between four $00 bytes and four $FF bytes
+    pub(crate) fn power_on_fceux(&mut self) {
+        for (addr, value) in self.vram.iter_mut().enumerate() {
+            *value = if addr & 4 == 0 { 0x00 } else { 0xFF };
+        }
+        self.ppu.set_position(0, 0);
+    }
+
+    // Overrides the region from the header, call before powering on
+    pub(crate) fn set_region(&mut self, region: Region) {
+        self.ppu.set_region(region);
+    }
+
     fn read_prg_rom(&self, mut addr: u16) -> u8 {
         addr -= 0x8000;
         if self.prg_rom.len() == 0x4000 && addr >= 0x4000 {
@@ -55,44 +128,98 @@ impl Bus<'_>{
         self.prg_rom[addr as usize]
     }
 
-    pub(crate) fn tick(&mut self, cycles: u8) {
-        self.cycles += cycles as usize;
+    pub(crate) fn cycles(&self) -> usize {
+        self.cycles
+    }
 
-        let nmi_before = self.ppu.nmi_interrupt.is_some();
-        // Cycles multiplied by 3 since the PPU clock runs 3 time faster than CPU clock
-        self.ppu.tick(cycles * 3);
-        let nmi_after = self.ppu.nmi_interrupt.is_some();
+    pub fn ram(&self) -> &[u8; 2048] {
+        &self.vram
+    }
 
-        if !nmi_before && nmi_after {
-            let action = (self.gameloop_callback)(&self.ppu, &mut self.joypad);
+    pub fn ppu(&self) -> &PPU {
+        &self.ppu
+    }
 
-            match action {
-                GameloopAction::NoAction => {},
-                GameloopAction::SaveState => self.save_state(),
-                GameloopAction::LoadState => self.load_state(),
-            }
+    // Port 0 is read from $4016 and port 1 from $4017
+    pub fn port(&self, port: usize) -> &Device {
+        &self.ports[port]
+    }
+
+    pub fn port_mut(&mut self, port: usize) -> &mut Device {
+        &mut self.ports[port]
+    }
+
+    pub fn connect(&mut self, port: usize, device: Device) {
+        self.ports[port] = device;
+    }
+
+    // Players 1 and 2 use controllers plugged straight into ports 1 and 2, players 3 and 4 need a Four Score
+    pub fn joypad(&self, player: usize) -> Option<&Joypad> {
+        match self.ports.get(player % 2)? {
+            Device::Controller(joypad) if player < 2 => Some(joypad),
+            Device::FourScore(four_score) => four_score.joypads.get(player / 2),
+            _ => None,
         }
     }
 
-    pub fn poll_nmi_status(&mut self) -> Option<u8> {
-        self.ppu.poll_nmi_interrupt()
+    pub fn joypad_mut(&mut self, player: usize) -> Option<&mut Joypad> {
+        match self.ports.get_mut(player % 2)? {
+            Device::Controller(joypad) if player < 2 => Some(joypad),
+            Device::FourScore(four_score) => four_score.joypads.get_mut(player / 2),
+            _ => None,
+        }
+    }
+
+    fn read_port(&mut self, port: usize) -> u8 {
+        if let Device::Zapper(zapper) = &mut self.ports[port] {
+            zapper.light = self.light_sensor.sees_light(&self.ppu, zapper.x, zapper.y);
+        }
+        self.ports[port].read()
+    }
+
+    // True once per frame, after the PPU has entered vblank
+    pub fn poll_frame_complete(&mut self) -> bool {
+        std::mem::take(&mut self.frame_complete)
     }
 
-    pub fn save_state(&mut self) {
-        let state = State::new(self.vram, self.ppu.clone());
-        state.save_state(1);
+    // The CPU registers live outside the bus, so they are passed in and handed back
+    pub fn state(&self, regs: Registers) -> State {
+        State {
+            regs,
+            ram: self.vram,
+            prg_ram: self.prg_ram,
+            cycles: self.cycles,
+            ppu: self.ppu.clone(),
+            ports: self.ports.clone(),
+        }
     }
 
-    pub fn load_state(&mut self) {
-        let state = State::load_state(1);
-        if let Ok(state) = state {
-            self.vram = state.ram;
-            self.ppu = state.ppu;
+    pub fn state_ref<'a>(&'a self, regs: &'a Registers) -> StateRef<'a> {
+        StateRef {
+            regs,
+            ram: &self.vram,
+            prg_ram: &self.prg_ram,
+            cycles: self.cycles,
+            ppu: &self.ppu,
+            ports: &self.ports,
         }
     }
+
+    pub fn set_state(&mut self, state: State) -> Registers {
+        self.vram = state.ram;
+        self.prg_ram = state.prg_ram;
+        self.cycles = state.cycles;
+        let chr_rom = std::mem::take(&mut self.ppu.chr_rom);
+        self.ppu = state.ppu;
+        self.ppu.chr_rom = chr_rom;
+        self.ports = state.ports;
+        self.frame_complete = false;
+        self.light_sensor.current = false;
+        state.regs
+    }
 }
 
-impl Memory for Bus<'_> {
+impl Memory for Bus {
     fn read(&mut self, addr: u16) -> u8 {
         match addr {
             // RAM Registers
@@ -115,8 +242,10 @@ impl Memory for Bus<'_> {
             // APU
             0x4000 ..= 0x4015 => 0, // Ignore APU
             // Joypad Controller
-            0x4016 => self.joypad.read(),
-            0x4017 => 0, // Second joypad
+            0x4016 => self.read_port(0),
+            0x4017 => self.read_port(1),
+            // PRG RAM
+            0x6000 ..= 0x7FFF => self.prg_ram[(addr - 0x6000) as usize],
             // PRG ROM Registers
             0x8000 ..= 0xFFFF => self.read_prg_rom(addr),
             _ => {
@@ -165,8 +294,14 @@ impl Memory for Bus<'_> {
                 self.ppu.write_oam_dma(&buffer);
             }
             // Joypad Controllers
-            0x4016 => self.joypad.write(value),
-            0x4017 =>  { /* Second joypad */ },
+            // The strobe is wired to both ports
+            0x4016 => {
+                self.ports[0].strobe(value);
+                self.ports[1].strobe(value);
+            }
+            0x4017 =>  { /* APU frame counter */ },
+            // PRG RAM
+            0x6000 ..= 0x7FFF => self.prg_ram[(addr - 0x6000) as usize] = value,
             // PRG ROM Registers
             0x8000 ..= 0xFFFF => {
                 panic!("Attempt to write to Cartridge ROM space")
@@ -177,20 +312,18 @@ impl Memory for Bus<'_> {
         }
     }
 
-    /*
-    NES CPU uses Little-Endian addressing rather than Big-Endian.
-    That means that the 8 least significant bits of an address will be stored before the 8 most significant bits.
-    */
-    fn read_16(&mut self, addr: u16) -> u16 {
-        let lo = self.read(addr) as u16;
-        let hi = self.read(addr + 1) as u16;
-        (hi << 8) | (lo as u16)
+    fn tick(&mut self, cycles: u8) {
+        self.cycles += cycles as usize;
+
+        // The PPU clock runs 3 times faster than the CPU clock, 3.2 times on PAL
+        let ppu_cycles = self.ppu.ppu_cycles(cycles);
+        if self.ppu.tick(ppu_cycles) {
+            self.frame_complete = true;
+            self.light_sensor.current = false;
+        }
     }
 
-    fn write_16(&mut self, addr: u16, value: u16) {
-        let hi = (value >> 8) as u8;
-        let lo = (value & 0xff) as u8;
-        self.write(addr, lo);
-        self.write(addr + 1, hi);
+    fn poll_nmi_status(&mut self) -> Option<u8> {
+        self.ppu.poll_nmi_interrupt()
     }
 }
\ No newline at end of file
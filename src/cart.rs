@@ -1,39 +1,192 @@
-use crate::lib::get_bit;
-use std::{fs::File, io::Read};
+use crate::archive;
+use crate::game_db::{GameDb, GameEntry};
+use crate::get_bit;
+use crate::patch::{self, PatchError};
+use md5::Md5;
+use sha1::{Digest, Sha1};
+use std::path::{Path, PathBuf};
+use std::{fmt, fs, io, str::FromStr};
 
 const NES_TAG: [u8; 4] = [0x4E, 0x45, 0x53, 0x1A];
 const PRG_ROM_PAGE_SIZE: usize = 16384;
 const CHR_ROM_PAGE_SIZE: usize = 8192;
+const HEADER_SIZE: usize = 16;
+const TRAINER_SIZE: usize = 512;
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+// Mappers the bus can run, Cart::load refuses the rest
+pub const SUPPORTED_MAPPERS: [u16; 1] = [0];
+
+// Problems with a ROM file's structure, found before anything is run
+#[derive(Debug)]
+pub enum CartError {
+    Io(io::Error),
+    Archive(String),
+    // The archive has no .nes file, or none with the name asked for
+    NoRomInArchive(Option<String>),
+    Patch { path: PathBuf, error: PatchError },
+    TooShort(usize),
+    NotINes,
+    UnknownVersion(u8),
+    NoPrgRom,
+    UnsupportedMapper(u16),
+    // The ROM sizes add up to more than any file could hold
+    TooLarge,
+    // The file ends before the ROM the header describes
+    Truncated { expected: usize, found: usize },
+    // The file carries data past the ROM the header describes
+    SizeMismatch { expected: usize, found: usize },
+}
+
+impl fmt::Display for CartError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CartError::Io(err) => write!(f, "{}", err),
+            CartError::Archive(err) => write!(f, "Unable to decompress: {}", err),
+            CartError::NoRomInArchive(Some(entry)) => write!(f, "Archive has no file named {}", entry),
+            CartError::NoRomInArchive(None) => write!(f, "Archive has no .nes file"),
+            CartError::Patch { path, error } => write!(f, "Unable to apply patch {}: {}", path.display(), error),
+            CartError::TooShort(size) => write!(f, "File is {} bytes, too short for a {} byte header", size, HEADER_SIZE),
+            CartError::NotINes => write!(f, "File is not in iNES file format"),
+            CartError::UnknownVersion(version) => write!(f, "Unknown iNES header version {}", version),
+            CartError::NoPrgRom => write!(f, "Header describes no PRG ROM"),
+            CartError::UnsupportedMapper(mapper) => write!(f, "Mapper {} ({}) is not supported", mapper, mapper_name(*mapper).unwrap_or("unknown")),
+            CartError::TooLarge => write!(f, "Header describes more ROM than a file can hold"),
+            CartError::Truncated { expected, found } => write!(f, "File is truncated, the header describes {} bytes but there are {}", expected, found),
+            CartError::SizeMismatch { expected, found } => write!(f, "File is {} bytes but the header describes {}", found, expected),
+        }
+    }
+}
+
+impl std::error::Error for CartError {}
+
+impl From<io::Error> for CartError {
+    fn from(err: io::Error) -> Self {
+        CartError::Io(err)
+    }
+}
+
+// Name of an iNES mapper number, for the common ones
+pub fn mapper_name(mapper: u16) -> Option<&'static str> {
+    Some(match mapper {
+        0 => "NROM",
+        1 => "MMC1",
+        2 => "UxROM",
+        3 => "CNROM",
+        4 => "MMC3",
+        5 => "MMC5",
+        7 => "AxROM",
+        9 => "MMC2",
+        10 => "MMC4",
+        11 => "Color Dreams",
+        13 => "CPROM",
+        19 => "Namco 163",
+        21 | 23 | 25 => "VRC4",
+        22 => "VRC2",
+        24 | 26 => "VRC6",
+        34 => "BNROM/NINA-001",
+        66 => "GxROM",
+        69 => "Sunsoft FME-7",
+        71 => "Camerica",
+        85 => "VRC7",
+        206 => "DxROM",
+        _ => return None,
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub enum Mirroring {
    Vertical,
    Horizontal,
    FourScreen,
 }
 
+// TV system, which sets the frame rate and how many scanlines make up a frame
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Region {
+    Ntsc,
+    Pal,
+}
+
+impl Region {
+    pub fn scanlines(self) -> u16 {
+        match self {
+            Region::Ntsc => 262,
+            Region::Pal => 312,
+        }
+    }
+
+    pub fn frame_rate(self) -> f64 {
+        match self {
+            Region::Ntsc => 60.0988,
+            Region::Pal => 50.007,
+        }
+    }
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "ntsc" => Ok(Region::Ntsc),
+            "pal" => Ok(Region::Pal),
+            _ => Err(format!("Unknown region {}, expected ntsc or pal", name)),
+        }
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Region::Ntsc => write!(f, "NTSC"),
+            Region::Pal => write!(f, "PAL"),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct RomHeader {
     prg_rom_start: usize,
     chr_rom_start: usize,
     prg_rom_size: usize,
     chr_rom_size: usize,
-    #[allow(dead_code)]
-    mapper: u8,
-    pub screen_mirroring: Mirroring,    
+    mapper: u16,
+    submapper: u8,
+    nes2: bool,
+    has_trainer: bool,
+    pub screen_mirroring: Mirroring,
+    // NES 2.0 default expansion device, 0 when unspecified or for iNES files
+    pub expansion_device: u8,
+    pub region: Region,
+    has_battery: bool,
+    // Set when data may legitimately follow the CHR ROM
+    has_extra_data: bool,
 }
 
 impl RomHeader {
-    fn new(buffer: &[u8]) -> Self {
+    pub fn parse(buffer: &[u8]) -> Result<Self, CartError> {
+        if buffer.len() < HEADER_SIZE {
+            return Err(CartError::TooShort(buffer.len()));
+        }
         if buffer[0..4] != NES_TAG {
-            panic!("File is not in iNES file format");
-        }   
-
-        let mapper = (buffer[7] & 0b1111_0000) | (buffer[6] >> 4);
+            return Err(CartError::NotINes);
+        }
 
         let ines_ver = (buffer[7] >> 2) & 0b11;
-        if ines_ver != 0 {
-            panic!("NES2.0 format is not supported");
+        let nes2 = match ines_ver {
+            0 | 1 => false,
+            2 => true,
+            _ => return Err(CartError::UnknownVersion(ines_ver)),
+        };
+        // Version 1 is left by old tools writing junk like "DiskDude!" over bytes 7-15, read those as zero
+        let mut header = [0; HEADER_SIZE];
+        header.copy_from_slice(&buffer[..HEADER_SIZE]);
+        if ines_ver == 1 {
+            header[7..].fill(0);
         }
+        let buffer = &header;
+
+        let mut mapper = ((buffer[7] & 0b1111_0000) | (buffer[6] >> 4)) as u16;
 
         let four_screen = get_bit(buffer[6], 3);
         let mirroring = get_bit(buffer[6], 0);
@@ -43,29 +196,179 @@ impl RomHeader {
             (false, false) => Mirroring::Horizontal,
         };
  
-        let prg_rom_size = buffer[4] as usize * PRG_ROM_PAGE_SIZE;
-        let chr_rom_size = buffer[5] as usize * CHR_ROM_PAGE_SIZE;
+        let mut prg_rom_size = buffer[4] as usize * PRG_ROM_PAGE_SIZE;
+        let mut chr_rom_size = buffer[5] as usize * CHR_ROM_PAGE_SIZE;
+        // PlayChoice-10 dumps carry the INST-ROM and PROM after the CHR ROM
+        let mut has_extra_data = get_bit(buffer[7], 1);
+        let mut submapper = 0;
+        let mut expansion_device = 0;
+        // iNES only has a rarely set PAL bit
+        let mut region = if get_bit(buffer[9], 0) { Region::Pal } else { Region::Ntsc };
+        // NES 2.0 adds the high bits of the mapper and ROM sizes, the timing and the default expansion device
+        if nes2 {
+            mapper |= ((buffer[8] & 0b1111) as u16) << 8;
+            submapper = buffer[8] >> 4;
+            prg_rom_size = nes2_rom_size(buffer[4], buffer[9] & 0b1111, PRG_ROM_PAGE_SIZE).ok_or(CartError::TooLarge)?;
+            chr_rom_size = nes2_rom_size(buffer[5], buffer[9] >> 4, CHR_ROM_PAGE_SIZE).ok_or(CartError::TooLarge)?;
+            // Console type 2 is PlayChoice-10, and byte 14 counts the miscellaneous ROMs after the CHR ROM
+            has_extra_data = buffer[7] & 0b11 == 2 || buffer[14] & 0b11 != 0;
+            // Multi-region games run as NTSC, and PAL is the closest we have to Dendy
+            region = match buffer[12] & 0b11 {
+                1 | 3 => Region::Pal,
+                _ => Region::Ntsc,
+            };
+            expansion_device = buffer[15] & 0b0011_1111;
+        }
+
+        if prg_rom_size == 0 {
+            return Err(CartError::NoPrgRom);
+        }
 
+        let has_battery = get_bit(buffer[6], 1);
         let has_trainer = get_bit(buffer[6], 2);
 
-        let prg_rom_start = 16 + if has_trainer { 512 } else { 0 };
-        let chr_rom_start = prg_rom_start + prg_rom_size;
+        let prg_rom_start = HEADER_SIZE + if has_trainer { TRAINER_SIZE } else { 0 };
+        let chr_rom_start = prg_rom_start.checked_add(prg_rom_size).ok_or(CartError::TooLarge)?;
+        // Checked here so file_size can't overflow
+        chr_rom_start.checked_add(chr_rom_size).filter(|&size| size <= isize::MAX as usize).ok_or(CartError::TooLarge)?;
 
-        Self {
+        Ok(Self {
             prg_rom_start,
             chr_rom_start,
             prg_rom_size,
             chr_rom_size,
             mapper,
-            screen_mirroring,   
+            submapper,
+            nes2,
+            has_trainer,
+            screen_mirroring,
+            expansion_device,
+            region,
+            has_battery,
+            has_extra_data,
+        })
+    }
+
+    // Size of the file the header describes, up to the end of the CHR ROM
+    pub fn file_size(&self) -> usize {
+        self.chr_rom_start + self.chr_rom_size
+    }
+
+    // Checks the header agrees with the size of the file it came from
+    pub fn check_size(&self, file_size: usize) -> Result<(), CartError> {
+        let expected = self.file_size();
+        if file_size < expected {
+            Err(CartError::Truncated { expected, found: file_size })
+        } else if file_size > expected && !self.has_extra_data {
+            Err(CartError::SizeMismatch { expected, found: file_size })
+        } else {
+            Ok(())
         }
-        
     }
 
+    pub fn prg_rom_size(&self) -> usize {
+        self.prg_rom_size
+    }
+
+    pub fn chr_rom_size(&self) -> usize {
+        self.chr_rom_size
+    }
+
+    pub fn mapper(&self) -> u16 {
+        self.mapper
+    }
+
+    pub fn submapper(&self) -> u8 {
+        self.submapper
+    }
+
+    pub fn is_nes2(&self) -> bool {
+        self.nes2
+    }
+
+    pub fn has_trainer(&self) -> bool {
+        self.has_trainer
+    }
+
+    pub fn has_battery(&self) -> bool {
+        self.has_battery
+    }
+
+    pub fn mapper_supported(&self) -> bool {
+        SUPPORTED_MAPPERS.contains(&self.mapper)
+    }
+
+    // Overrides the fields a game database entry sets, returning the names of the ones that changed
+    pub fn correct(&mut self, entry: &GameEntry) -> Vec<&'static str> {
+        fn set<T: PartialEq>(field: &mut T, value: Option<T>, name: &'static str, corrected: &mut Vec<&'static str>) {
+            match value {
+                Some(value) if *field != value => {
+                    *field = value;
+                    corrected.push(name);
+                }
+                _ => {}
+            }
+        }
+
+        let mut corrected = Vec::new();
+        set(&mut self.mapper, entry.mapper, "mapper", &mut corrected);
+        set(&mut self.submapper, entry.submapper, "submapper", &mut corrected);
+        set(&mut self.screen_mirroring, entry.mirroring.clone(), "mirroring", &mut corrected);
+        set(&mut self.has_battery, entry.battery, "battery", &mut corrected);
+        set(&mut self.region, entry.region, "region", &mut corrected);
+        set(&mut self.expansion_device, entry.expansion_device, "expansion device", &mut corrected);
+        corrected
+    }
+}
+
+/*
+    NES 2.0 ROM sizes. Normally the most significant nibble extends the page count, but when it is 0xF
+    the least significant byte is instead an exponent and multiplier, for sizes that aren't whole pages.
+*/
+fn nes2_rom_size(lsb: u8, msb: u8, page_size: usize) -> Option<usize> {
+    if msb == 0b1111 {
+        let exponent = (lsb >> 2) as u32;
+        let multiplier = (lsb & 0b11) as usize * 2 + 1;
+        1usize.checked_shl(exponent)?.checked_mul(multiplier)
+    } else {
+        ((msb as usize) << 8 | lsb as usize).checked_mul(page_size)
+    }
+}
+
+// How a ROM file is turned into a Cart
+#[derive(Clone, Debug)]
+pub struct LoadOptions {
+    // Correct the header from the built in game database
+    pub game_db: bool,
+    // File to load from a zip archive, otherwise the first .nes file
+    pub archive_entry: Option<String>,
+    // IPS, UPS or BPS patch to apply
+    pub patch: Option<PathBuf>,
+    // Without a patch given, apply one found next to the ROM
+    pub find_patch: bool,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions {
+            game_db: true,
+            archive_entry: None,
+            patch: None,
+            find_patch: true,
+        }
+    }
+}
+
+impl LoadOptions {
+    // The patch that will be applied to a ROM, if any
+    pub fn patch_path(&self, file_path: &Path) -> Option<PathBuf> {
+        self.patch.clone().or_else(|| self.find_patch.then(|| patch::find(file_path)).flatten())
+    }
 }
 
 #[allow(dead_code)]
-pub(crate) struct Cart {
+#[derive(Clone)]
+pub struct Cart {
     pub filename: String,
     pub rom_size: usize,
     pub prg_rom: Vec<u8>,
@@ -74,27 +377,155 @@ pub(crate) struct Cart {
 }
 
 impl Cart {
-    pub(crate) fn new(file_path: &str) -> Self {
-        let mut rom_data = Vec::new();
-        let rom_size: usize;
-        // Open file inside its own scope so it is dropped when file is read into buffer
-        {
-            let mut file = File::open(file_path).unwrap_or_else(|_| panic!("Unable to open file {}", file_path));
-            // read the whole file
-            rom_size = file.read_to_end(&mut rom_data).unwrap_or_else(|_| panic!("Unable to read file {}", file_path));
-        }
-        
-        let header = RomHeader::new(&rom_data);
+    pub fn new(file_path: &str) -> Self {
+        Cart::load(file_path).unwrap_or_else(|err| panic!("Unable to load {}: {}", file_path, err))
+    }
+
+    pub fn load(file_path: &str) -> Result<Self, CartError> {
+        Cart::load_with(file_path, &LoadOptions::default())
+    }
+
+    pub fn load_with(file_path: &str, options: &LoadOptions) -> Result<Self, CartError> {
+        let mut cart = Cart::from_bytes(file_path, Cart::read(file_path, options)?)?;
+        if options.game_db {
+            cart.apply_game_db();
+        }
+        if !cart.rom_header.mapper_supported() {
+            return Err(CartError::UnsupportedMapper(cart.rom_header.mapper()));
+        }
+        Ok(cart)
+    }
+
+    // Reads a ROM file, unpacking it from an archive and patching it
+    pub fn read(file_path: &str, options: &LoadOptions) -> Result<Vec<u8>, CartError> {
+        let rom_data = archive::unpack(fs::read(file_path)?, options.archive_entry.as_deref())?;
+        match options.patch_path(Path::new(file_path)) {
+            Some(path) => patch::apply_file(&path, &rom_data).map_err(|error| CartError::Patch { path, error }),
+            None => Ok(rom_data),
+        }
+    }
+
+    // Keeps the header as it is in the file. Data after the CHR ROM is ignored, use RomHeader::check_size to find it
+    pub fn from_bytes(filename: &str, rom_data: Vec<u8>) -> Result<Self, CartError> {
+        let header = RomHeader::parse(&rom_data)?;
+        if let Err(err @ CartError::Truncated { .. }) = header.check_size(rom_data.len()) {
+            return Err(err);
+        }
         let prg_rom = rom_data[header.prg_rom_start..(header.prg_rom_start + header.prg_rom_size)].to_vec();
         let chr_rom = rom_data[header.chr_rom_start..(header.chr_rom_start + header.chr_rom_size)].to_vec();
 
-        Self{
-            filename: file_path.to_string(),
-            rom_size,
+        Ok(Self{
+            filename: filename.to_string(),
+            rom_size: rom_data.len(),
             prg_rom,
             chr_rom,
             rom_header: header
-        }
+        })
+    }
+
+    // CRC32 of the PRG and CHR ROM, identifies the game independently of its header
+    pub fn crc32(&self) -> u32 {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&self.prg_rom);
+        hasher.update(&self.chr_rom);
+        hasher.finalize()
+    }
+
+    // Corrects the header if the game is in the built in database, returning its entry
+    pub fn apply_game_db(&mut self) -> Option<&'static GameEntry> {
+        let entry = GameDb::builtin().find(self.crc32(), &self.sha1())?;
+        self.rom_header.correct(entry);
+        Some(entry)
+    }
+
+    // SHA-1 of the PRG and CHR ROM, as used by ROM databases alongside the CRC32
+    pub fn sha1(&self) -> [u8; 20] {
+        let mut hasher = Sha1::new();
+        hasher.update(&self.prg_rom);
+        hasher.update(&self.chr_rom);
+        hasher.finalize().into()
+    }
+
+    // MD5 of the PRG and CHR ROM, which FCEUX identifies ROMs by
+    pub fn md5(&self) -> [u8; 16] {
+        let mut hasher = Md5::new();
+        hasher.update(&self.prg_rom);
+        hasher.update(&self.chr_rom);
+        hasher.finalize().into()
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::{Bus, Memory};
+
+    fn rom(header: [u8; 12], size: usize) -> Vec<u8> {
+        let mut data = NES_TAG.to_vec();
+        data.extend(header);
+        data.resize(size, 0);
+        data
+    }
+
+    #[test]
+    fn test_header_problems() {
+        assert!(matches!(RomHeader::parse(&NES_TAG), Err(CartError::TooShort(4))));
+        assert!(matches!(RomHeader::parse(&[0; 16]), Err(CartError::NotINes)));
+        assert!(matches!(RomHeader::parse(&rom([1, 1, 0, 0b1100, 0, 0, 0, 0, 0, 0, 0, 0], 16)), Err(CartError::UnknownVersion(3))));
+        assert!(matches!(RomHeader::parse(&rom([0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], 16)), Err(CartError::NoPrgRom)));
+
+        // 16 KiB of PRG ROM, 8 KiB of CHR ROM, a battery and a trainer
+        let data = rom([1, 1, 0b0110, 0, 0, 0, 0, 0, 0, 0, 0, 0], 16 + 512 + 16384 + 8192);
+        let header = RomHeader::parse(&data).unwrap();
+        assert!(header.has_battery() && header.has_trainer() && header.mapper_supported());
+        assert!(header.check_size(data.len()).is_ok());
+        assert!(matches!(header.check_size(data.len() + 1), Err(CartError::SizeMismatch { .. })));
+        assert!(matches!(
+            Cart::from_bytes("truncated.nes", data[..data.len() - 1].to_vec()),
+            Err(CartError::Truncated { expected: 25104, found: 25103 })
+        ));
+        assert!(Cart::from_bytes("rom.nes", data).is_ok());
+
+        // "DiskDude!" over bytes 7-15 is ignored rather than read as a mapper and PAL flag
+        let mut data = rom([1, 1, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0], 16 + 16384 + 8192);
+        data[7..16].copy_from_slice(b"DiskDude!");
+        let header = RomHeader::parse(&data).unwrap();
+        assert!(!header.is_nes2());
+        assert_eq!(header.mapper(), 0);
+        assert_eq!(header.region, Region::Ntsc);
+        assert!(header.check_size(data.len()).is_ok());
+    }
+
+    #[test]
+    fn test_unsupported_mapper() {
+        let path = std::env::temp_dir().join(format!("mmc1-{}.nes", std::process::id()));
+        fs::write(&path, rom([2, 1, 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0], 16 + 32768 + 8192)).unwrap();
+        let result = Cart::load(&path.to_string_lossy());
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(CartError::UnsupportedMapper(1))));
+    }
+
+    #[test]
+    fn test_nes2_rom_size() {
+        // 4 MiB of PRG ROM through the page count, and 3 * 2^10 bytes of CHR ROM through the exponent notation
+        let header = RomHeader::parse(&rom([0, 10 << 2 | 1, 0x10, 0b1000, 0x04, 0xF1, 0, 0, 0, 0, 0, 0], 16)).unwrap();
+        assert!(header.is_nes2());
+        assert_eq!(header.mapper(), 0x401);
+        assert_eq!(mapper_name(header.mapper()), None);
+        assert_eq!(header.prg_rom_size(), 256 * PRG_ROM_PAGE_SIZE);
+        assert_eq!(header.chr_rom_size(), 3 << 10);
+
+        // 8 KiB of PRG ROM repeats through the 32 KiB the CPU sees
+        let mut data = rom([13 << 2, 1, 0, 0b1000, 0, 0x0F, 0, 0, 0, 0, 0, 0], 16 + 8192 + 8192);
+        data[16 + 8191] = 0xAB;
+        let mut bus = Bus::new(Cart::from_bytes("8k.nes", data).unwrap());
+        assert_eq!(bus.read(0x9FFF), 0xAB);
+        assert_eq!(bus.read(0xFFFF), 0xAB);
+
+        // 7 * 2^63 bytes of PRG ROM
+        assert!(matches!(RomHeader::parse(&rom([0xFF, 0, 0, 0b1000, 0, 0x0F, 0, 0, 0, 0, 0, 0], 16)), Err(CartError::TooLarge)));
+        // 2^62 bytes each of PRG and CHR ROM, too much only once added together
+        assert!(matches!(RomHeader::parse(&rom([62 << 2, 62 << 2, 0, 0b1000, 0, 0xFF, 0, 0, 0, 0, 0, 0], 16)), Err(CartError::TooLarge)));
+    }
+}
@@ -0,0 +1,169 @@
+/*
+    Command line options for the SDL frontend. Everything clap can check is checked while parsing, the
+    rest (files that must exist, options that only make sense together) straight after, so mistakes
+    are reported with clap's usage message before a window opens.
+*/
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use clap::builder::RangedU64ValueParser;
+use clap::error::ErrorKind;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use nes_emulator_rs::cart::{LoadOptions, Region};
+use nes_emulator_rs::devices::DeviceType;
+use nes_emulator_rs::nes::PLAYERS;
+use nes_emulator_rs::slots::SLOT_COUNT;
+
+pub struct Options {
+    pub rom: PathBuf,
+    pub info: bool,
+    pub load: LoadOptions,
+    pub scale: u32,
+    pub fullscreen: bool,
+    pub region: Option<Region>,
+    pub palette: Option<PathBuf>,
+    pub load_slot: Option<u8>,
+    pub record: Option<String>,
+    pub play: Option<String>,
+    // Run this many frames without a window, then exit
+    pub headless: Option<usize>,
+    pub screenshot: Option<PathBuf>,
+    // Frame to take the screenshot after, otherwise it is taken on exit
+    pub screenshot_frame: Option<usize>,
+    pub devices: [Option<DeviceType>; 2],
+    // Player for each gamepad GUID
+    pub pads: HashMap<String, usize>,
+    pub bindings: Option<PathBuf>,
+    pub binds: Vec<String>,
+    pub turbo_rate: Option<usize>,
+}
+
+fn command() -> Command {
+    Command::new("nes-emulator-rs")
+        .about("NES emulator")
+        .arg(Arg::new("rom").value_name("ROM").required(true).value_parser(value_parser!(PathBuf))
+            .help("iNES or NES 2.0 ROM file, which can be in a .zip or .gz file"))
+        .arg(Arg::new("entry").long("entry").value_name("NAME")
+            .help("File to load from a zip archive, by path or name, instead of the first .nes file"))
+        .arg(Arg::new("info").long("info").action(ArgAction::SetTrue)
+            .help("Print the ROM header and hashes and exit, with status 1 if the file is malformed"))
+        .arg(Arg::new("patch").long("patch").value_name("FILE").value_parser(value_parser!(PathBuf))
+            .help("IPS, UPS or BPS patch to apply, instead of one next to the ROM with the same name"))
+        .arg(Arg::new("no-patch").long("no-patch").action(ArgAction::SetTrue).conflicts_with("patch")
+            .help("Don't apply a patch found next to the ROM"))
+        .arg(Arg::new("no-game-db").long("no-game-db").action(ArgAction::SetTrue)
+            .help("Use the ROM header as it is, without corrections from the built in game database"))
+        .arg(Arg::new("scale").long("scale").value_name("FACTOR").default_value("2")
+            .value_parser(RangedU64ValueParser::<u32>::new().range(1..=8))
+            .help("Window size as a multiple of 256x240"))
+        .arg(Arg::new("fullscreen").long("fullscreen").action(ArgAction::SetTrue)
+            .help("Start fullscreen"))
+        .arg(Arg::new("region").long("region").value_name("REGION").value_parser(value_parser!(Region))
+            .help("Override the ROM's region: ntsc or pal"))
+        .arg(Arg::new("palette").long("palette").value_name("FILE").value_parser(value_parser!(PathBuf))
+            .help("Palette file of 64 RGB colours (.pal)"))
+        .arg(Arg::new("mute").long("mute").action(ArgAction::SetTrue)
+            .help("Disable sound output (sound is not emulated yet, so this currently changes nothing)"))
+        .arg(Arg::new("load-slot").long("load-slot").value_name("SLOT")
+            .value_parser(RangedU64ValueParser::<u8>::new().range(0..SLOT_COUNT as u64)).conflicts_with("play")
+            .help("Load a save state slot on start"))
+        .arg(Arg::new("record").long("record").value_name("MOVIE").conflicts_with("play")
+            .help("Record input from power-on, or from the state loaded with --load-slot, to a movie, saved on exit. .fm2 files are written as FM2"))
+        .arg(Arg::new("play").long("play").value_name("MOVIE")
+            .help("Play back a movie. .fm2 files are read as FM2"))
+        .arg(Arg::new("headless").long("headless").value_name("FRAMES").value_parser(value_parser!(usize))
+            .help("Run this many frames without a window, then exit"))
+        .arg(Arg::new("screenshot").long("screenshot").value_name("PNG").value_parser(value_parser!(PathBuf))
+            .help("Save a screenshot, on exit unless --screenshot-frame is given"))
+        .arg(Arg::new("screenshot-frame").long("screenshot-frame").value_name("FRAME")
+            .value_parser(RangedU64ValueParser::<usize>::new().range(1..)).requires("screenshot")
+            .help("Take the screenshot after this many frames"))
+        .arg(Arg::new("port1").long("port1").value_name("DEVICE").value_parser(value_parser!(DeviceType))
+            .help("Device in port 1: controller, zapper, arkanoid, powerpad, fourscore or none"))
+        .arg(Arg::new("port2").long("port2").value_name("DEVICE").value_parser(value_parser!(DeviceType))
+            .help("Device in port 2"))
+        .arg(Arg::new("pad").long("pad").value_name("PLAYER=GUID").action(ArgAction::Append)
+            .help("Give the gamepad with this GUID a fixed player"))
+        .arg(Arg::new("bindings").long("bindings").value_name("FILE").value_parser(value_parser!(PathBuf))
+            .help("Bindings file to use instead of the one in the config directory"))
+        .arg(Arg::new("bind").long("bind").value_name("NAME=KEY").action(ArgAction::Append)
+            .help("Override a binding for this run, e.g. player1.a=J"))
+        .arg(Arg::new("turbo-rate").long("turbo-rate").value_name("PRESSES")
+            .value_parser(RangedU64ValueParser::<usize>::new().range(1..=30))
+            .help("Turbo presses per second"))
+}
+
+pub fn parse() -> Options {
+    let mut command = command();
+    let matches = command.get_matches_mut();
+    match Options::from_matches(&matches) {
+        Ok(options) => options,
+        Err((kind, message)) => command.error(kind, message).exit(),
+    }
+}
+
+impl Options {
+    fn from_matches(matches: &ArgMatches) -> Result<Self, (ErrorKind, String)> {
+        let rom: PathBuf = matches.get_one::<PathBuf>("rom").unwrap().clone();
+        if !rom.is_file() {
+            return Err((ErrorKind::ValueValidation, format!("ROM {} does not exist", rom.display())));
+        }
+        let palette = matches.get_one::<PathBuf>("palette").cloned();
+        if let Some(palette) = palette.as_ref().filter(|palette| !palette.is_file()) {
+            return Err((ErrorKind::ValueValidation, format!("Palette {} does not exist", palette.display())));
+        }
+        let patch = matches.get_one::<PathBuf>("patch").cloned();
+        if let Some(patch) = patch.as_ref().filter(|patch| !patch.is_file()) {
+            return Err((ErrorKind::ValueValidation, format!("Patch {} does not exist", patch.display())));
+        }
+        let play = matches.get_one::<String>("play").cloned();
+        if let Some(play) = play.as_ref().filter(|play| !PathBuf::from(play).is_file()) {
+            return Err((ErrorKind::ValueValidation, format!("Movie {} does not exist", play)));
+        }
+
+        let headless = matches.get_one::<usize>("headless").copied();
+        let screenshot_frame = matches.get_one::<usize>("screenshot-frame").copied();
+        if let (Some(frames), Some(frame)) = (headless, screenshot_frame) {
+            if frame > frames {
+                return Err((ErrorKind::ArgumentConflict, format!("--screenshot-frame {} is after the last of the {} headless frames", frame, frames)));
+            }
+        }
+
+        let mut pads = HashMap::new();
+        for pad in matches.get_many::<String>("pad").into_iter().flatten() {
+            let player = pad.split_once('=')
+                .and_then(|(player, guid)| Some((player.parse::<usize>().ok()?, guid)))
+                .filter(|(player, _)| (1..=PLAYERS).contains(player));
+            let Some((player, guid)) = player else {
+                return Err((ErrorKind::ValueValidation, format!("--pad expects <player>=<guid> with a player from 1 to {}, got {}", PLAYERS, pad)));
+            };
+            pads.insert(guid.to_string(), player - 1);
+        }
+
+        Ok(Options {
+            rom,
+            info: matches.get_flag("info"),
+            load: LoadOptions {
+                game_db: !matches.get_flag("no-game-db"),
+                archive_entry: matches.get_one::<String>("entry").cloned(),
+                patch,
+                find_patch: !matches.get_flag("no-patch"),
+            },
+            scale: *matches.get_one::<u32>("scale").unwrap(),
+            fullscreen: matches.get_flag("fullscreen"),
+            region: matches.get_one::<Region>("region").copied(),
+            palette,
+            load_slot: matches.get_one::<u8>("load-slot").copied(),
+            record: matches.get_one::<String>("record").cloned(),
+            play,
+            headless,
+            screenshot: matches.get_one::<PathBuf>("screenshot").cloned(),
+            screenshot_frame,
+            devices: [matches.get_one::<DeviceType>("port1").copied(), matches.get_one::<DeviceType>("port2").copied()],
+            pads,
+            bindings: matches.get_one::<PathBuf>("bindings").cloned(),
+            binds: matches.get_many::<String>("bind").into_iter().flatten().cloned().collect(),
+            turbo_rate: matches.get_one::<usize>("turbo-rate").copied(),
+        })
+    }
+}
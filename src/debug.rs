@@ -1,7 +1,7 @@
-use crate::{cpu::{AddressingMode, CPU}, bus::Memory};
+use crate::{cpu::{AddressingMode, CPU}, bus::Memory, instructions::INSTRUCTIONS};
 
 #[allow(dead_code)]
-impl<'a> CPU<'a> {
+impl<M: Memory> CPU<M> {
     pub fn get_absolute_address(&mut self, mode: &AddressingMode, addr: u16) -> (u16, bool) {
         match mode {
             AddressingMode::Immediate => (addr, false),
@@ -34,7 +34,7 @@ impl<'a> CPU<'a> {
             AddressingMode::IndirectX => {
                 let base = self.read(addr);
  
-                let ptr: u8 = (base as u8).wrapping_add(self.regs.x);
+                let ptr: u8 = base.wrapping_add(self.regs.x);
                 let lo = self.read(ptr as u16);
                 let hi = self.read(ptr.wrapping_add(1) as u16);
                 ((hi as u16) << 8 | (lo as u16), false)
@@ -43,7 +43,7 @@ impl<'a> CPU<'a> {
                 let base = self.read(addr);
  
                 let lo = self.read(base as u16);
-                let hi = self.read((base as u8).wrapping_add(1) as u16);
+                let hi = self.read(base.wrapping_add(1) as u16);
                 let deref_base = (hi as u16) << 8 | (lo as u16);
                 let deref = deref_base.wrapping_add(self.regs.y as u16);
 
@@ -56,10 +56,10 @@ impl<'a> CPU<'a> {
         }
     }
 
-    pub fn trace(self: &mut CPU<'a>) -> String {
+    pub fn trace(&mut self) -> String {
 
         let code = self.read(self.regs.pc);
-        let ops = self.get_instruction(code);
+        let ops = &INSTRUCTIONS[code as usize];
 
         let begin = self.regs.pc;
         let mut hex_dump = vec![code];
@@ -0,0 +1,370 @@
+/*
+    Devices that plug into the two controller ports. Every write to $4016 strobes both ports, and
+    reads from $4016 and $4017 return bits 0-4 from the device in port 1 and port 2 respectively.
+*/
+use std::fmt;
+use std::str::FromStr;
+
+use crate::get_bit;
+use crate::joypad::Joypad;
+
+pub trait PortDevice {
+    // Bits 0-4 of the value read from the port
+    fn read(&mut self) -> u8;
+
+    // Called with every value written to $4016
+    fn strobe(&mut self, data: u8);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DeviceType {
+    None,
+    Controller,
+    Zapper,
+    Arkanoid,
+    PowerPad,
+    // Takes up both ports
+    FourScore,
+}
+
+impl FromStr for DeviceType {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "none" => Ok(DeviceType::None),
+            "controller" => Ok(DeviceType::Controller),
+            "zapper" => Ok(DeviceType::Zapper),
+            "arkanoid" => Ok(DeviceType::Arkanoid),
+            "powerpad" => Ok(DeviceType::PowerPad),
+            "fourscore" => Ok(DeviceType::FourScore),
+            _ => Err(format!("Unknown device {}, expected none, controller, zapper, arkanoid, powerpad or fourscore", name)),
+        }
+    }
+}
+
+impl fmt::Display for DeviceType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            DeviceType::None => "none",
+            DeviceType::Controller => "controller",
+            DeviceType::Zapper => "zapper",
+            DeviceType::Arkanoid => "arkanoid",
+            DeviceType::PowerPad => "powerpad",
+            DeviceType::FourScore => "fourscore",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl DeviceType {
+    // Devices for both ports from the NES 2.0 default expansion device, byte 15 of the header
+    pub fn from_expansion_device(device: u8) -> Option<[DeviceType; 2]> {
+        match device {
+            0x01 => Some([DeviceType::Controller, DeviceType::Controller]),
+            0x02 => Some([DeviceType::FourScore, DeviceType::FourScore]),
+            0x08 => Some([DeviceType::Controller, DeviceType::Zapper]),
+            0x09 => Some([DeviceType::Zapper, DeviceType::Zapper]),
+            0x0B | 0x0C => Some([DeviceType::Controller, DeviceType::PowerPad]),
+            0x0F => Some([DeviceType::Controller, DeviceType::Arkanoid]),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub enum Device {
+    None,
+    Controller(Joypad),
+    Zapper(Zapper),
+    Arkanoid(ArkanoidPaddle),
+    PowerPad(PowerPad),
+    FourScore(FourScore),
+}
+
+impl Device {
+    pub fn new(device_type: DeviceType, port: usize) -> Self {
+        match device_type {
+            DeviceType::None => Device::None,
+            DeviceType::Controller => Device::Controller(Joypad::new()),
+            DeviceType::Zapper => Device::Zapper(Zapper::default()),
+            DeviceType::Arkanoid => Device::Arkanoid(ArkanoidPaddle::default()),
+            DeviceType::PowerPad => Device::PowerPad(PowerPad::default()),
+            DeviceType::FourScore => Device::FourScore(FourScore::new(port)),
+        }
+    }
+
+    pub fn device_type(&self) -> DeviceType {
+        match self {
+            Device::None => DeviceType::None,
+            Device::Controller(_) => DeviceType::Controller,
+            Device::Zapper(_) => DeviceType::Zapper,
+            Device::Arkanoid(_) => DeviceType::Arkanoid,
+            Device::PowerPad(_) => DeviceType::PowerPad,
+            Device::FourScore(_) => DeviceType::FourScore,
+        }
+    }
+}
+
+impl PortDevice for Device {
+    fn read(&mut self) -> u8 {
+        match self {
+            Device::None => 0,
+            Device::Controller(joypad) => joypad.read(),
+            Device::Zapper(zapper) => zapper.read(),
+            Device::Arkanoid(paddle) => paddle.read(),
+            Device::PowerPad(pad) => pad.read(),
+            Device::FourScore(four_score) => four_score.read(),
+        }
+    }
+
+    fn strobe(&mut self, data: u8) {
+        match self {
+            Device::None => {}
+            Device::Controller(joypad) => joypad.strobe(data),
+            Device::Zapper(zapper) => zapper.strobe(data),
+            Device::Arkanoid(paddle) => paddle.strobe(data),
+            Device::PowerPad(pad) => pad.strobe(data),
+            Device::FourScore(four_score) => four_score.strobe(data),
+        }
+    }
+}
+
+/*
+    Light gun. D3 is 0 while the sensor sees light and D4 is 1 while the trigger is pulled.
+    The bus decides whether there is light where it is aimed before each read, in Bus::read_port through
+    LightSensor::sees_light.
+*/
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct Zapper {
+    // Screen position the gun is aimed at
+    pub x: usize,
+    pub y: usize,
+    pub trigger: bool,
+    pub(crate) light: bool,
+}
+
+impl Zapper {
+    pub fn aim(&mut self, x: usize, y: usize) {
+        self.x = x;
+        self.y = y;
+    }
+}
+
+impl PortDevice for Zapper {
+    fn read(&mut self) -> u8 {
+        ((!self.light as u8) << 3) | ((self.trigger as u8) << 4)
+    }
+
+    fn strobe(&mut self, _data: u8) {}
+}
+
+/*
+    Arkanoid Vaus paddle. The knob position is latched by the strobe and shifted out inverted, most
+    significant bit first, on D4. D3 is 1 while the button is held.
+*/
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ArkanoidPaddle {
+    pub position: u8,
+    pub button: bool,
+    strobe: bool,
+    latch: u8,
+    bit_index: u8,
+}
+
+impl ArkanoidPaddle {
+    // The knob only turns through part of its range
+    pub const MIN_POSITION: u8 = 0x62;
+    pub const MAX_POSITION: u8 = 0xF2;
+
+    // Maps a screen x coordinate onto the knob's range
+    pub fn move_to(&mut self, x: usize) {
+        let range = (ArkanoidPaddle::MAX_POSITION - ArkanoidPaddle::MIN_POSITION) as usize;
+        self.position = ArkanoidPaddle::MIN_POSITION + (x.min(255) * range / 255) as u8;
+    }
+}
+
+impl Default for ArkanoidPaddle {
+    fn default() -> Self {
+        ArkanoidPaddle {
+            position: ArkanoidPaddle::MIN_POSITION,
+            button: false,
+            strobe: false,
+            latch: 0,
+            bit_index: 0,
+        }
+    }
+}
+
+impl PortDevice for ArkanoidPaddle {
+    fn read(&mut self) -> u8 {
+        let bit = if self.bit_index < 8 { (!self.latch >> (7 - self.bit_index)) & 1 } else { 0 };
+        if !self.strobe && self.bit_index < 8 {
+            self.bit_index += 1;
+        }
+        (bit << 4) | ((self.button as u8) << 3)
+    }
+
+    fn strobe(&mut self, data: u8) {
+        self.strobe = get_bit(data, 0);
+        if self.strobe {
+            self.latch = self.position;
+            self.bit_index = 0;
+        }
+    }
+}
+
+/*
+    Power Pad mat with 12 buttons, numbered 1-12 from the top left on side B. Button n is bit n - 1 of `buttons`.
+    The buttons are shifted out on two lines at once: D3 gives 2, 1, 5, 9, 6, 10, 11, 7 and D4 gives 4, 3, 12, 8
+    then 1s. Both lines read 1 after eight reads.
+*/
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct PowerPad {
+    pub buttons: u16,
+    strobe: bool,
+    bit_index: u8,
+}
+
+impl PowerPad {
+    const D3_ORDER: [u8; 8] = [2, 1, 5, 9, 6, 10, 11, 7];
+    const D4_ORDER: [u8; 4] = [4, 3, 12, 8];
+
+    pub fn set_button_pressed_status(&mut self, button: u8, pressed: bool) {
+        assert!((1..=12).contains(&button), "Power Pad button {} does not exist", button);
+        if pressed {
+            self.buttons |= 1 << (button - 1);
+        } else {
+            self.buttons &= !(1 << (button - 1));
+        }
+    }
+
+    fn pressed(&self, button: u8) -> u8 {
+        ((self.buttons >> (button - 1)) & 1) as u8
+    }
+}
+
+impl PortDevice for PowerPad {
+    fn read(&mut self) -> u8 {
+        let index = self.bit_index as usize;
+        let d3 = PowerPad::D3_ORDER.get(index).map_or(1, |&button| self.pressed(button));
+        let d4 = PowerPad::D4_ORDER.get(index).map_or(1, |&button| self.pressed(button));
+        if !self.strobe && self.bit_index < 8 {
+            self.bit_index += 1;
+        }
+        (d3 << 3) | (d4 << 4)
+    }
+
+    fn strobe(&mut self, data: u8) {
+        self.strobe = get_bit(data, 0);
+        if self.strobe {
+            self.bit_index = 0;
+        }
+    }
+}
+
+/*
+    One port's side of the Four Score adapter, port 1 carries players 1 and 3 and port 2 players 2 and 4.
+    Reads shift out the first controller's eight buttons, then the second's, then a signature that tells
+    games the adapter is there: $10 on port 1 and $20 on port 2, sent most significant bit first.
+    Further reads return 1.
+*/
+#[derive(Clone, Deserialize, Serialize)]
+pub struct FourScore {
+    pub joypads: [Joypad; 2],
+    signature: u8,
+    strobe: bool,
+    bit_index: u8,
+}
+
+impl FourScore {
+    pub fn new(port: usize) -> Self {
+        FourScore {
+            joypads: [Joypad::new(), Joypad::new()],
+            signature: if port == 0 { 0x10 } else { 0x20 },
+            strobe: false,
+            bit_index: 0,
+        }
+    }
+}
+
+impl PortDevice for FourScore {
+    fn read(&mut self) -> u8 {
+        let bit = match self.bit_index {
+            0..=7 => self.joypads[0].buttons() >> self.bit_index,
+            8..=15 => self.joypads[1].buttons() >> (self.bit_index - 8),
+            // Games shift the signature in from the most significant bit
+            16..=23 => self.signature >> (23 - self.bit_index),
+            _ => return 1,
+        } & 1;
+        if !self.strobe {
+            self.bit_index += 1;
+        }
+        bit
+    }
+
+    fn strobe(&mut self, data: u8) {
+        self.strobe = get_bit(data, 0);
+        if self.strobe {
+            self.bit_index = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_bits(device: &mut impl PortDevice, reads: usize) -> Vec<u8> {
+        device.strobe(1);
+        device.strobe(0);
+        (0..reads).map(|_| device.read()).collect()
+    }
+
+    #[test]
+    fn test_arkanoid_paddle() {
+        let mut paddle = ArkanoidPaddle { position: 0b1010_0000, button: true, ..Default::default() };
+        let bits = read_bits(&mut paddle, 8);
+        let d4: Vec<u8> = bits.iter().map(|bits| (bits >> 4) & 1).collect();
+        assert_eq!(d4, [0, 1, 0, 1, 1, 1, 1, 1]);
+        assert!(bits.iter().all(|bits| bits & 0x08 == 0x08));
+
+        paddle.button = false;
+        assert_eq!(paddle.read() & 0x08, 0);
+    }
+
+    #[test]
+    fn test_power_pad() {
+        let mut pad = PowerPad::default();
+        pad.set_button_pressed_status(1, true);
+        pad.set_button_pressed_status(12, true);
+        let bits = read_bits(&mut pad, 9);
+        let d3: Vec<u8> = bits.iter().map(|bits| (bits >> 3) & 1).collect();
+        let d4: Vec<u8> = bits.iter().map(|bits| (bits >> 4) & 1).collect();
+        assert_eq!(d3, [0, 1, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(d4, [0, 0, 1, 0, 1, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_four_score() {
+        let mut four_score = FourScore::new(1);
+        four_score.joypads[0].set_buttons(0b0000_0001);
+        four_score.joypads[1].set_buttons(0b1000_0000);
+        let bits = read_bits(&mut four_score, 25);
+        assert_eq!(bits[..16], [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+        // $20, most significant bit first
+        assert_eq!(bits[16..], [0, 0, 1, 0, 0, 0, 0, 0, 1]);
+
+        let mut four_score = FourScore::new(0);
+        assert_eq!(read_bits(&mut four_score, 25)[16..], [0, 0, 0, 1, 0, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_zapper() {
+        let mut zapper = Zapper { trigger: true, ..Default::default() };
+        assert_eq!(zapper.read(), 0b11000);
+        zapper.light = true;
+        zapper.trigger = false;
+        assert_eq!(zapper.read(), 0);
+    }
+}
@@ -0,0 +1,422 @@
+/*
+    Gym-style reinforcement learning environment on top of the headless Nes.
+
+    Rewards, terminal conditions and extra info values are read from work RAM, as described by a
+    per-game TOML config, e.g. for Super Mario Bros.:
+
+        frame_skip = 4
+        sticky_action_probability = 0.25
+        observation = "greyscale"
+        downsample = 2
+
+        [[reward]]
+        address = 0x07DD
+        length = 6
+        encoding = "digits"
+
+        [[done]]
+        address = 0x075A
+        value = 0xFF
+
+        [[info]]
+        name = "lives"
+        address = 0x075A
+*/
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::cart::Cart;
+use crate::nes::Nes;
+use crate::rendering::Frame;
+use crate::{WINDOW_HEIGHT, WINDOW_WIDTH};
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ObservationType {
+    // 256x240 RGB, 3 bytes per pixel
+    Rgb,
+    // Luminance averaged over downsample x downsample blocks
+    Greyscale,
+    // The 2 KiB of work RAM
+    Ram,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    // Little-endian binary
+    Binary,
+    // Packed BCD, most significant byte first
+    Bcd,
+    // One decimal digit per byte, most significant digit first
+    Digits,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RamValue {
+    #[serde(default)]
+    pub name: String,
+    pub address: u16,
+    #[serde(default = "default_length")]
+    pub length: u16,
+    #[serde(default = "default_encoding")]
+    pub encoding: Encoding,
+}
+
+impl RamValue {
+    // Longest value that fits in an i64: 8 bytes, or 18 decimal digits
+    fn max_length(&self) -> u16 {
+        match self.encoding {
+            Encoding::Binary => 8,
+            Encoding::Bcd => 9,
+            Encoding::Digits => 18,
+        }
+    }
+
+    pub fn read(&self, ram: &[u8; 2048]) -> i64 {
+        let bytes = (0..self.length).map(|i| ram[(self.address.wrapping_add(i) & 0x07FF) as usize]);
+        match self.encoding {
+            Encoding::Binary => bytes.rev().fold(0, |acc, b| (acc << 8) | b as i64),
+            Encoding::Bcd => bytes.fold(0, |acc, b| acc * 100 + (b >> 4) as i64 * 10 + (b & 0x0F) as i64),
+            // Wrapping, as bytes above 9 that aren't digits could still overflow 18 of them
+            Encoding::Digits => bytes.fold(0i64, |acc, b| acc.wrapping_mul(10).wrapping_add(b as i64)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Reward {
+    #[serde(flatten)]
+    pub value: RamValue,
+    // The reward is the change in the value since the last step, multiplied by the scale
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Done {
+    #[serde(flatten)]
+    pub value: RamValue,
+    #[serde(rename = "value")]
+    pub target: i64,
+    #[serde(default = "default_comparison")]
+    pub comparison: Comparison,
+}
+
+impl Done {
+    fn is_done(&self, ram: &[u8; 2048]) -> bool {
+        let value = self.value.read(ram);
+        match self.comparison {
+            Comparison::Equal => value == self.target,
+            Comparison::NotEqual => value != self.target,
+            Comparison::Less => value < self.target,
+            Comparison::Greater => value > self.target,
+        }
+    }
+}
+
+fn default_length() -> u16 { 1 }
+fn default_encoding() -> Encoding { Encoding::Binary }
+fn default_scale() -> f64 { 1.0 }
+fn default_comparison() -> Comparison { Comparison::Equal }
+fn default_frame_skip() -> usize { 4 }
+fn default_observation() -> ObservationType { ObservationType::Rgb }
+fn default_downsample() -> usize { 2 }
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct EnvConfig {
+    // Frames each action is held for, rewards are summed over them
+    #[serde(default = "default_frame_skip")]
+    pub frame_skip: usize,
+    // Chance each frame of repeating the previous action instead of the new one
+    #[serde(default)]
+    pub sticky_action_probability: f64,
+    #[serde(default = "default_observation")]
+    pub observation: ObservationType,
+    #[serde(default = "default_downsample")]
+    pub downsample: usize,
+    // Frames run with no input after a reset, to skip boot screens
+    #[serde(default)]
+    pub reset_frames: usize,
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default)]
+    pub reward: Vec<Reward>,
+    #[serde(default)]
+    pub done: Vec<Done>,
+    #[serde(default)]
+    pub info: Vec<RamValue>,
+}
+
+impl EnvConfig {
+    pub fn load(path: &str) -> Result<Self> {
+        let config = fs::read_to_string(path)?;
+        Self::parse(&config)
+    }
+
+    pub fn parse(config: &str) -> Result<Self> {
+        let config: EnvConfig = toml::from_str(config).map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+        if config.frame_skip == 0 || config.downsample == 0 {
+            return Err(Error::new(ErrorKind::InvalidData, "frame_skip and downsample must be at least 1"));
+        }
+        let values = config.reward.iter().map(|r| &r.value).chain(config.done.iter().map(|d| &d.value)).chain(&config.info);
+        for value in values {
+            if value.length > value.max_length() {
+                let message = format!("RAM value at {:#06X} is {} bytes long, {:?} values can be at most {}",
+                    value.address, value.length, value.encoding, value.max_length());
+                return Err(Error::new(ErrorKind::InvalidData, message));
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct StepInfo {
+    // Frames run since the last reset
+    pub frame: usize,
+    // The values listed under [[info]], keyed by name
+    pub values: HashMap<String, i64>,
+}
+
+pub struct NesEnv {
+    nes: Nes,
+    config: EnvConfig,
+    rng: StdRng,
+    last_action: u8,
+    reward_values: Vec<i64>,
+    frame: usize,
+}
+
+impl NesEnv {
+    pub fn new(cart: Cart, config: EnvConfig) -> Self {
+        let rng = StdRng::seed_from_u64(config.seed);
+        NesEnv {
+            nes: Nes::with_seed(cart, config.seed),
+            config,
+            rng,
+            last_action: 0,
+            reward_values: Vec::new(),
+            frame: 0,
+        }
+    }
+
+    pub fn nes(&self) -> &Nes {
+        &self.nes
+    }
+
+    pub fn config(&self) -> &EnvConfig {
+        &self.config
+    }
+
+    pub fn reset(&mut self) -> Vec<u8> {
+        self.nes.power_cycle();
+        self.last_action = 0;
+        self.frame = 0;
+        for _ in 0..self.config.reset_frames {
+            self.nes.run_frame();
+        }
+        self.reward_values = self.read_reward_values();
+        self.observation()
+    }
+
+    // The action is a button mask as taken by Nes::set_buttons
+    pub fn step(&mut self, action: u8) -> (Vec<u8>, f64, bool, StepInfo) {
+        let (reward, done) = self.advance(action);
+        (self.observation(), reward, done, self.info())
+    }
+
+    // Runs the frames for one step without building the observation, returning the reward and whether the episode ended
+    pub fn advance(&mut self, action: u8) -> (f64, bool) {
+        let mut reward = 0.0;
+        let mut done = false;
+
+        for _ in 0..self.config.frame_skip {
+            if self.config.sticky_action_probability == 0.0 || self.rng.gen::<f64>() >= self.config.sticky_action_probability {
+                self.last_action = action;
+            }
+            self.nes.set_buttons(0, self.last_action);
+            self.nes.run_frame();
+            self.frame += 1;
+
+            reward += self.collect_reward();
+            done = self.config.done.iter().any(|d| d.is_done(self.nes.ram()));
+            if done {
+                break;
+            }
+        }
+
+        (reward, done)
+    }
+
+    fn read_reward_values(&self) -> Vec<i64> {
+        self.config.reward.iter().map(|r| r.value.read(self.nes.ram())).collect()
+    }
+
+    fn collect_reward(&mut self) -> f64 {
+        let ram = self.nes.ram();
+        let mut reward = 0.0;
+        for (r, before) in self.config.reward.iter().zip(self.reward_values.iter_mut()) {
+            let now = r.value.read(ram);
+            reward += (now - *before) as f64 * r.scale;
+            *before = now;
+        }
+        reward
+    }
+
+    pub fn info(&self) -> StepInfo {
+        StepInfo {
+            frame: self.frame,
+            values: self.config.info.iter().map(|v| (v.name.clone(), v.read(self.nes.ram()))).collect(),
+        }
+    }
+
+    pub fn observation_size(&self) -> usize {
+        match self.config.observation {
+            ObservationType::Rgb => WINDOW_WIDTH * WINDOW_HEIGHT * 3,
+            ObservationType::Greyscale => (WINDOW_WIDTH / self.config.downsample) * (WINDOW_HEIGHT / self.config.downsample),
+            ObservationType::Ram => 2048,
+        }
+    }
+
+    pub fn observation(&self) -> Vec<u8> {
+        let mut observation = vec![0; self.observation_size()];
+        self.write_observation(&mut observation);
+        observation
+    }
+
+    // Fills a buffer of observation_size() bytes
+    pub fn write_observation(&self, out: &mut [u8]) {
+        match self.config.observation {
+            ObservationType::Rgb => rgb(self.nes.framebuffer(), out),
+            ObservationType::Greyscale => greyscale(self.nes.framebuffer(), self.config.downsample, out),
+            ObservationType::Ram => out.copy_from_slice(self.nes.ram()),
+        }
+    }
+}
+
+// The frame buffer is twice the screen width, only the visible 256x240 is copied
+pub fn rgb(frame: &Frame, out: &mut [u8]) {
+    let row = WINDOW_WIDTH * 3;
+    for (y, out_row) in out.chunks_exact_mut(row).take(WINDOW_HEIGHT).enumerate() {
+        let start = y * Frame::WIDTH * 3;
+        out_row.copy_from_slice(&frame.data[start..start + row]);
+    }
+}
+
+pub fn greyscale(frame: &Frame, downsample: usize, out: &mut [u8]) {
+    let block = (downsample * downsample) as u32;
+    let mut pixels = out.iter_mut();
+    for y in (0..WINDOW_HEIGHT - WINDOW_HEIGHT % downsample).step_by(downsample) {
+        for x in (0..WINDOW_WIDTH - WINDOW_WIDTH % downsample).step_by(downsample) {
+            let mut sum = 0;
+            for dy in 0..downsample {
+                for dx in 0..downsample {
+                    let base = (y + dy) * Frame::WIDTH * 3 + (x + dx) * 3;
+                    let (r, g, b) = (frame.data[base] as u32, frame.data[base + 1] as u32, frame.data[base + 2] as u32);
+                    sum += (299 * r + 587 * g + 114 * b) / 1000;
+                }
+            }
+            if let Some(pixel) = pixels.next() {
+                *pixel = (sum / block) as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::nestest_cart;
+
+    #[test]
+    fn test_read_ram_value() {
+        let mut ram = [0; 2048];
+        ram[0x10..0x13].copy_from_slice(&[0x01, 0x02, 0x03]);
+
+        let mut value = RamValue { name: String::new(), address: 0x10, length: 3, encoding: Encoding::Binary };
+        assert_eq!(value.read(&ram), 0x030201);
+        value.encoding = Encoding::Bcd;
+        assert_eq!(value.read(&ram), 10203);
+        value.encoding = Encoding::Digits;
+        assert_eq!(value.read(&ram), 123);
+
+        // Addresses wrap around the end of the address space onto mirrored RAM
+        ram[0x7FF] = 0x04;
+        ram[0] = 0x05;
+        let value = RamValue { name: String::new(), address: 0xFFFF, length: 2, encoding: Encoding::Binary };
+        assert_eq!(value.read(&ram), 0x0504);
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = EnvConfig::parse(r#"
+            frame_skip = 2
+            observation = "ram"
+
+            [[reward]]
+            address = 0x07DD
+            length = 6
+            encoding = "digits"
+            scale = 0.5
+
+            [[done]]
+            address = 0x075A
+            value = 0xFF
+            comparison = "equal"
+        "#).unwrap();
+
+        assert_eq!(config.frame_skip, 2);
+        assert_eq!(config.observation, ObservationType::Ram);
+        assert_eq!(config.reward[0].value.address, 0x07DD);
+        assert_eq!(config.reward[0].value.encoding, Encoding::Digits);
+        assert_eq!(config.done[0].target, 0xFF);
+        assert!(EnvConfig::parse("frame_skip = 0").is_err());
+
+        // Values too long for an i64 are rejected
+        let value = |length: u16, encoding: &str| format!("[[info]]\naddress = 0\nlength = {}\nencoding = \"{}\"", length, encoding);
+        assert!(EnvConfig::parse(&value(8, "binary")).is_ok());
+        assert!(EnvConfig::parse(&value(9, "binary")).is_err());
+        assert!(EnvConfig::parse(&value(9, "bcd")).is_ok());
+        assert!(EnvConfig::parse(&value(10, "bcd")).is_err());
+        assert!(EnvConfig::parse(&value(18, "digits")).is_ok());
+        assert!(EnvConfig::parse(&value(19, "digits")).is_err());
+    }
+
+    #[test]
+    fn test_step() {
+        let config = EnvConfig::parse(r#"
+            observation = "greyscale"
+            downsample = 4
+            sticky_action_probability = 0.25
+
+            [[info]]
+            name = "first"
+            address = 0x0000
+        "#).unwrap();
+        let mut env = NesEnv::new(nestest_cart(), config);
+
+        let observation = env.reset();
+        assert_eq!(observation.len(), 64 * 60);
+
+        let (observation, reward, done, info) = env.step(0);
+        assert_eq!(observation.len(), 64 * 60);
+        assert_eq!(reward, 0.0);
+        assert!(!done);
+        assert_eq!(info.frame, 4);
+        assert!(info.values.contains_key("first"));
+    }
+}
@@ -0,0 +1,123 @@
+/*
+    Corrections for ROMs with a wrong iNES header, so bad dumps play properly without being patched.
+    Entries are keyed by the CRC32 or SHA-1 of the PRG and CHR ROM, which don't depend on the header,
+    and override only the fields they set. The database is game_db.toml, built into the emulator:
+
+        [[game]]
+        name = "Example (USA)"
+        crc32 = "0123ABCD"
+        sha1 = "0123456789ABCDEF0123456789ABCDEF01234567"
+        mapper = 1
+        submapper = 0
+        mirroring = "Vertical"
+        battery = true
+        region = "Pal"
+        expansion_device = 0x08
+
+    `mirroring` is Horizontal, Vertical or FourScreen, `region` is Ntsc or Pal and `expansion_device`
+    is the NES 2.0 default expansion device, which picks what is plugged into the ports.
+
+    Only entries checked against a dump at hand are shipped, so far just nestest, which has a correct
+    header and keeps the lookup tested. tools/nes20db_to_toml.py converts the NES 2.0 header database
+    (nes20db.xml) into entries to fill it with corrections for real bad dumps.
+*/
+use std::sync::OnceLock;
+
+use crate::cart::{Mirroring, Region};
+
+const DATABASE: &str = include_str!("game_db.toml");
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GameEntry {
+    pub name: String,
+    pub crc32: Option<String>,
+    pub sha1: Option<String>,
+    pub mapper: Option<u16>,
+    pub submapper: Option<u8>,
+    pub mirroring: Option<Mirroring>,
+    pub battery: Option<bool>,
+    pub region: Option<Region>,
+    pub expansion_device: Option<u8>,
+}
+
+impl GameEntry {
+    // Takes the hashes as hex, so a lookup formats them once rather than once per entry
+    fn matches(&self, crc32: &str, sha1: &str) -> bool {
+        self.crc32.as_ref().is_some_and(|entry| entry.eq_ignore_ascii_case(crc32))
+            || self.sha1.as_ref().is_some_and(|entry| entry.eq_ignore_ascii_case(sha1))
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct GameDb {
+    #[serde(default, rename = "game")]
+    games: Vec<GameEntry>,
+}
+
+impl GameDb {
+    pub fn parse(text: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+
+    // The database built into the emulator
+    pub fn builtin() -> &'static GameDb {
+        static BUILTIN: OnceLock<GameDb> = OnceLock::new();
+        BUILTIN.get_or_init(|| GameDb::parse(DATABASE).expect("Built in game database is invalid"))
+    }
+
+    pub fn find(&self, crc32: u32, sha1: &[u8; 20]) -> Option<&GameEntry> {
+        let crc32 = format!("{:08X}", crc32);
+        let sha1: String = sha1.iter().map(|byte| format!("{:02X}", byte)).collect();
+        self.games.iter().find(|game| game.matches(&crc32, &sha1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::nestest_cart;
+
+    #[test]
+    fn test_corrections() {
+        // Every entry needs a key, and the built in database has to parse
+        assert!(GameDb::builtin().games.iter().all(|game| game.crc32.is_some() || game.sha1.is_some()));
+
+        // nestest's header is right, so its shipped entry matches without changing anything
+        let mut cart = nestest_cart();
+        let entry = GameDb::builtin().find(cart.crc32(), &cart.sha1()).unwrap();
+        assert_eq!(entry.name, "nestest");
+        assert_eq!(entry.sha1.as_deref(), Some("4131307F0F69F2A5C54B7D438328C5B2A5ED0820"));
+        assert!(cart.rom_header.correct(entry).is_empty());
+
+        let mut cart = nestest_cart();
+        let crc32 = format!("{:08x}", cart.crc32());
+        let db = GameDb::parse(&format!(r#"
+            [[game]]
+            name = "Someone else"
+            crc32 = "00000000"
+            mapper = 4
+
+            [[game]]
+            name = "nestest"
+            crc32 = "{}"
+            mirroring = "Vertical"
+            region = "Pal"
+            expansion_device = 0x08
+        "#, crc32)).unwrap();
+
+        let entry = db.find(cart.crc32(), &cart.sha1()).unwrap();
+        assert_eq!(entry.name, "nestest");
+        assert!(db.find(cart.crc32() ^ 1, &[0; 20]).is_none());
+
+        let corrected = cart.rom_header.correct(entry);
+        assert_eq!(corrected, ["mirroring", "region", "expansion device"]);
+        assert_eq!(cart.rom_header.screen_mirroring, Mirroring::Vertical);
+        assert_eq!(cart.rom_header.region, Region::Pal);
+        assert_eq!(cart.rom_header.expansion_device, 0x08);
+        assert_eq!(cart.rom_header.mapper(), 0);
+
+        // Fields that already agree aren't reported
+        assert!(cart.rom_header.correct(entry).is_empty());
+    }
+}
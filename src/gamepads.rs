@@ -0,0 +1,170 @@
+/*
+    USB gamepads for the SDL frontend. Pads are opened and closed as they are plugged in and out, SDL also
+    reports the ones already connected at startup as being plugged in.
+
+    Each pad drives one player. A pad goes to the player it was assigned with --pad, otherwise to the player
+    it last drove, otherwise to the first player without a pad. Players 3 and 4 need a Four Score.
+*/
+use std::collections::HashMap;
+
+use nes_emulator_rs::joypad::Inputs;
+use nes_emulator_rs::nes::{Nes, PLAYERS};
+use nes_emulator_rs::turbo::Turbo;
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::{GameControllerSubsystem, JoystickSubsystem};
+
+use crate::bindings::PadButton;
+
+// How far the left stick has to move, out of 32767, before it counts as a d-pad press
+pub const STICK_DEADZONE: i16 = 10000;
+
+struct Pad {
+    controller: GameController,
+    guid: String,
+    player: usize,
+    // Directions held on the d-pad and on the stick, a direction is pressed while either holds it
+    dpad: [bool; 4],
+    stick: [bool; 4],
+}
+
+pub struct Gamepads {
+    controllers: GameControllerSubsystem,
+    joysticks: JoystickSubsystem,
+    button_map: HashMap<Button, PadButton>,
+    pads: Vec<Pad>,
+    // Player for each pad GUID, from --pad and from pads that have been unplugged
+    assigned: HashMap<String, usize>,
+}
+
+impl Gamepads {
+    pub fn new(
+        controllers: GameControllerSubsystem,
+        joysticks: JoystickSubsystem,
+        button_map: HashMap<Button, PadButton>,
+        assigned: HashMap<String, usize>,
+    ) -> Self {
+        Gamepads {
+            controllers,
+            joysticks,
+            button_map,
+            pads: Vec::new(),
+            assigned,
+        }
+    }
+
+    // Returns true if the event was a gamepad event
+    pub fn handle_event(&mut self, nes: &mut Nes, turbo: &mut Turbo, event: &Event) -> bool {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => self.add(which),
+            Event::ControllerDeviceRemoved { which, .. } => self.remove(nes, turbo, which),
+            Event::ControllerButtonDown { which, button, .. } => self.press(nes, turbo, which, button, true),
+            Event::ControllerButtonUp { which, button, .. } => self.press(nes, turbo, which, button, false),
+            Event::ControllerAxisMotion { which, axis, value, .. } => self.move_stick(nes, which, axis, value),
+            _ => return false,
+        }
+        true
+    }
+
+    // SDL gives the joystick index here, every other event gives the instance id
+    fn add(&mut self, index: u32) {
+        let Ok(controller) = self.controllers.open(index) else {
+            return;
+        };
+        if self.pads.iter().any(|pad| pad.controller.instance_id() == controller.instance_id()) {
+            return;
+        }
+        let guid = self.joysticks.device_guid(index).map(|guid| guid.string()).unwrap_or_default();
+
+        let taken = |player: usize| self.pads.iter().any(|pad| pad.player == player);
+        let player = match self.assigned.get(&guid) {
+            Some(&player) if !taken(player) => player,
+            _ => match (0..PLAYERS).find(|&player| !taken(player)) {
+                Some(player) => player,
+                None => {
+                    println!("Gamepad {} ignored, every player already has one", controller.name());
+                    return;
+                }
+            },
+        };
+
+        println!("Gamepad {} ({}) connected as player {}", controller.name(), guid, player + 1);
+        self.pads.push(Pad {
+            controller,
+            guid,
+            player,
+            dpad: [false; 4],
+            stick: [false; 4],
+        });
+    }
+
+    // Lets go of everything the pad was holding so the player isn't left walking
+    fn remove(&mut self, nes: &mut Nes, turbo: &mut Turbo, instance_id: u32) {
+        let Some(index) = self.pads.iter().position(|pad| pad.controller.instance_id() == instance_id) else {
+            return;
+        };
+        let pad = self.pads.remove(index);
+        for button in Turbo::BUTTONS {
+            turbo.set_held(nes, pad.player, button, false);
+        }
+        for input in Inputs::ALL {
+            nes.set_button_pressed_status(pad.player, input, false);
+        }
+        println!("Gamepad {} disconnected from player {}", pad.controller.name(), pad.player + 1);
+        self.assigned.entry(pad.guid).or_insert(pad.player);
+    }
+
+    fn pad_mut(&mut self, instance_id: u32) -> Option<&mut Pad> {
+        self.pads.iter_mut().find(|pad| pad.controller.instance_id() == instance_id)
+    }
+
+    fn press(&mut self, nes: &mut Nes, turbo: &mut Turbo, instance_id: u32, button: Button, pressed: bool) {
+        let Some(&PadButton { input, turbo: is_turbo }) = self.button_map.get(&button) else {
+            return;
+        };
+        let Some(pad) = self.pad_mut(instance_id) else {
+            return;
+        };
+        if is_turbo {
+            turbo.set_held(nes, pad.player, input, pressed);
+            return;
+        }
+        let pressed = match direction(input) {
+            Some(direction) => {
+                pad.dpad[direction] = pressed;
+                pressed || pad.stick[direction]
+            }
+            None => pressed,
+        };
+        nes.set_button_pressed_status(pad.player, input, pressed);
+    }
+
+    fn move_stick(&mut self, nes: &mut Nes, instance_id: u32, axis: Axis, value: i16) {
+        let (negative, positive) = match axis {
+            Axis::LeftX => (Inputs::Left, Inputs::Right),
+            Axis::LeftY => (Inputs::Up, Inputs::Down),
+            _ => return,
+        };
+        let Some(pad) = self.pad_mut(instance_id) else {
+            return;
+        };
+        for (input, held) in [(negative, value < -STICK_DEADZONE), (positive, value > STICK_DEADZONE)] {
+            let direction = direction(input).unwrap();
+            if pad.stick[direction] == held {
+                continue;
+            }
+            pad.stick[direction] = held;
+            nes.set_button_pressed_status(pad.player, input, held || pad.dpad[direction]);
+        }
+    }
+}
+
+fn direction(input: Inputs) -> Option<usize> {
+    match input {
+        Inputs::Up => Some(0),
+        Inputs::Down => Some(1),
+        Inputs::Left => Some(2),
+        Inputs::Right => Some(3),
+        _ => None,
+    }
+}
@@ -0,0 +1,122 @@
+use crate::joypad::Inputs;
+use crate::nes::Nes;
+
+// Buttons held for a number of frames
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct MacroStep {
+    pub inputs: Vec<Inputs>,
+    pub frames: usize,
+}
+
+impl MacroStep {
+    fn mask(&self) -> u8 {
+        self.inputs.iter().fold(0, |mask, input| mask | (1 << input.bit()))
+    }
+}
+
+/*
+    A sequence of inputs for one controller, recorded from play or written by hand, that can be replayed
+    with a single key. Handy for menu navigation and other sequences that get repeated while testing.
+*/
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct InputMacro {
+    pub steps: Vec<MacroStep>,
+}
+
+impl InputMacro {
+    // Length in frames
+    pub fn len(&self) -> usize {
+        self.steps.iter().map(|step| step.frames).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Records one frame of input, bit 0 is A through to bit 7 for Right
+    pub fn push_frame(&mut self, buttons: u8) {
+        match self.steps.last_mut() {
+            Some(step) if step.mask() == buttons => step.frames += 1,
+            _ => self.steps.push(MacroStep {
+                inputs: Inputs::ALL.into_iter().filter(|input| buttons & (1 << input.bit()) != 0).collect(),
+                frames: 1,
+            }),
+        }
+    }
+
+    // Drops the idle frames before the first press and after the last release
+    pub fn trim(&mut self) {
+        while self.steps.last().is_some_and(|step| step.inputs.is_empty()) {
+            self.steps.pop();
+        }
+        let idle = self.steps.iter().take_while(|step| step.inputs.is_empty()).count();
+        self.steps.drain(..idle);
+    }
+
+    // The buttons held on a frame, or None past the end
+    pub fn buttons_at(&self, mut frame: usize) -> Option<u8> {
+        for step in &self.steps {
+            if frame < step.frames {
+                return Some(step.mask());
+            }
+            frame -= step.frames;
+        }
+        None
+    }
+}
+
+// Replays a macro on one player's controller, overriding whatever else is held until it finishes
+pub struct MacroPlayer {
+    input_macro: InputMacro,
+    player: usize,
+    frame: usize,
+}
+
+impl MacroPlayer {
+    pub fn new(input_macro: InputMacro, player: usize) -> Self {
+        MacroPlayer { input_macro, player, frame: 0 }
+    }
+
+    // Call once per frame, before running it. Returns false once the macro has finished and let go of everything
+    pub fn apply(&mut self, nes: &mut Nes) -> bool {
+        let buttons = self.input_macro.buttons_at(self.frame);
+        for input in Inputs::ALL {
+            let pressed = buttons.is_some_and(|buttons| buttons & (1 << input.bit()) != 0);
+            nes.set_button_pressed_status(self.player, input, pressed);
+        }
+        self.frame += 1;
+        buttons.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::nestest;
+
+    #[test]
+    fn test_record_and_replay() {
+        let mut input_macro = InputMacro::default();
+        for buttons in [0, 0, 0b1000, 0b1000, 0b1001, 0, 0b10_0000, 0] {
+            input_macro.push_frame(buttons);
+        }
+        input_macro.trim();
+        assert_eq!(input_macro.steps, [
+            MacroStep { inputs: vec![Inputs::Start], frames: 2 },
+            MacroStep { inputs: vec![Inputs::A, Inputs::Start], frames: 1 },
+            MacroStep { inputs: vec![], frames: 1 },
+            MacroStep { inputs: vec![Inputs::Down], frames: 1 },
+        ]);
+
+        let mut nes = nestest();
+        nes.set_buttons(0, 0b1000_0000);
+        let mut player = MacroPlayer::new(input_macro, 0);
+        let mut played = Vec::new();
+        while player.apply(&mut nes) {
+            played.push(nes.buttons(0));
+            nes.run_frame();
+        }
+        assert_eq!(played, [0b1000, 0b1000, 0b1001, 0, 0b10_0000]);
+        assert_eq!(nes.buttons(0), 0);
+    }
+}
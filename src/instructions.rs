@@ -1,11 +1,12 @@
 use crate::bus::Memory;
-use crate::lib::{get_bit, set_bit};
+use crate::{get_bit, set_bit};
 use crate::cpu::{CPU, AddressingMode};
 use crate::registers::CPUStatusFlags;
 
 const STACK: u16 = 0x0100;
 pub const STACK_RESET: u8 = 0xFD;
 
+#[derive(Clone, Copy)]
 pub struct Instruction {
     pub addr_mode: AddressingMode,
     pub name: &'static str,
@@ -13,327 +14,449 @@ pub struct Instruction {
     pub cycles: u8,
 }
 
-impl CPU<'_> {
-    pub(crate) fn get_instruction(&self, opcode: u8) -> Instruction {
-        match opcode {
-            0x69 => Instruction { addr_mode: AddressingMode::Immediate, name: "ADC", bytes: 2, cycles: 2 },
-            0x65 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "ADC", bytes: 2, cycles: 3 },
-            0x75 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "ADC", bytes: 2, cycles: 4 },
-            0x6D => Instruction { addr_mode: AddressingMode::Absolute, name: "ADC", bytes: 3, cycles: 4 },
-            0x7D => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "ADC", bytes: 3, cycles: 4 /* +1 if page crossed */ }, 
-            0x79 => Instruction { addr_mode: AddressingMode::AbsoluteY, name: "ADC", bytes: 3, cycles: 4 /* +1 if page crossed */ },
-            0x61 => Instruction { addr_mode: AddressingMode::IndirectX, name: "ADC", bytes: 2, cycles: 6 },
-            0x71 => Instruction { addr_mode: AddressingMode::IndirectY, name: "ADC", bytes: 2, cycles: 5 /* +1 if page crossed */ },
-
-            0x29 => Instruction { addr_mode: AddressingMode::Immediate, name: "AND", bytes: 2, cycles: 2 },
-            0x25 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "AND", bytes: 2, cycles: 3 },
-            0x35 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "AND", bytes: 2, cycles: 4 },
-            0x2D => Instruction { addr_mode: AddressingMode::Absolute, name: "AND", bytes: 3, cycles: 4 },
-            0x3D => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "AND", bytes: 3, cycles: 4 /* +1 if page crossed */ },
-            0x39 => Instruction { addr_mode: AddressingMode::AbsoluteY, name: "AND", bytes: 3, cycles: 4 /* +1 if page crossed */ },
-            0x21 => Instruction { addr_mode: AddressingMode::IndirectX, name: "AND", bytes: 2, cycles: 6 },
-            0x31 => Instruction { addr_mode: AddressingMode::IndirectY, name: "AND", bytes: 2, cycles: 5 /* +1 if page crossed */ },
-
-            0x0A => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "ASL_ACC", bytes: 1, cycles: 2 },
-            0x06 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "ASL", bytes: 2, cycles: 5 },
-            0x16 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "ASL", bytes: 2, cycles: 6 },
-            0x0E => Instruction { addr_mode: AddressingMode::Absolute, name: "ASL", bytes: 3, cycles: 6 },
-            0x1E => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "ASL", bytes: 3, cycles: 7 },
-
-            0x90 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "BCC", bytes: 2, cycles: 2 /* +1 if branch succeeds, +2 if to a new page */ },
-            0xB0 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "BCS", bytes: 2, cycles: 2 /* +1 if branch succeeds, +2 if to a new page */ },
-            0xF0 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "BEQ", bytes: 2, cycles: 2 /* +1 if branch succeeds, +2 if to a new page */ },
-            
-            0x24 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "BIT", bytes: 2, cycles: 3 },
-            0x2C => Instruction { addr_mode: AddressingMode::Absolute, name: "BIT", bytes: 3, cycles: 4 },
+pub type Handler<M> = fn(&mut CPU<M>, &AddressingMode);
+
+// Opcode metadata shared by the executor and the disassembler
+pub static INSTRUCTIONS: [Instruction; 256] = {
+    let mut table = [decode(0x00); 256];
+    let mut opcode = 0;
+    while opcode < 256 {
+        table[opcode] = decode(opcode as u8);
+        opcode += 1;
+    }
+    table
+};
+
+const fn decode(opcode: u8) -> Instruction {
+    match opcode {
+        0x69 => Instruction { addr_mode: AddressingMode::Immediate, name: "ADC", bytes: 2, cycles: 2 },
+        0x65 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "ADC", bytes: 2, cycles: 3 },
+        0x75 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "ADC", bytes: 2, cycles: 4 },
+        0x6D => Instruction { addr_mode: AddressingMode::Absolute, name: "ADC", bytes: 3, cycles: 4 },
+        0x7D => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "ADC", bytes: 3, cycles: 4 /* +1 if page crossed */ }, 
+        0x79 => Instruction { addr_mode: AddressingMode::AbsoluteY, name: "ADC", bytes: 3, cycles: 4 /* +1 if page crossed */ },
+        0x61 => Instruction { addr_mode: AddressingMode::IndirectX, name: "ADC", bytes: 2, cycles: 6 },
+        0x71 => Instruction { addr_mode: AddressingMode::IndirectY, name: "ADC", bytes: 2, cycles: 5 /* +1 if page crossed */ },
+
+        0x29 => Instruction { addr_mode: AddressingMode::Immediate, name: "AND", bytes: 2, cycles: 2 },
+        0x25 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "AND", bytes: 2, cycles: 3 },
+        0x35 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "AND", bytes: 2, cycles: 4 },
+        0x2D => Instruction { addr_mode: AddressingMode::Absolute, name: "AND", bytes: 3, cycles: 4 },
+        0x3D => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "AND", bytes: 3, cycles: 4 /* +1 if page crossed */ },
+        0x39 => Instruction { addr_mode: AddressingMode::AbsoluteY, name: "AND", bytes: 3, cycles: 4 /* +1 if page crossed */ },
+        0x21 => Instruction { addr_mode: AddressingMode::IndirectX, name: "AND", bytes: 2, cycles: 6 },
+        0x31 => Instruction { addr_mode: AddressingMode::IndirectY, name: "AND", bytes: 2, cycles: 5 /* +1 if page crossed */ },
+
+        0x0A => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "ASL_ACC", bytes: 1, cycles: 2 },
+        0x06 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "ASL", bytes: 2, cycles: 5 },
+        0x16 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "ASL", bytes: 2, cycles: 6 },
+        0x0E => Instruction { addr_mode: AddressingMode::Absolute, name: "ASL", bytes: 3, cycles: 6 },
+        0x1E => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "ASL", bytes: 3, cycles: 7 },
+
+        0x90 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "BCC", bytes: 2, cycles: 2 /* +1 if branch succeeds, +2 if to a new page */ },
+        0xB0 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "BCS", bytes: 2, cycles: 2 /* +1 if branch succeeds, +2 if to a new page */ },
+        0xF0 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "BEQ", bytes: 2, cycles: 2 /* +1 if branch succeeds, +2 if to a new page */ },
         
-            0x30 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "BMI", bytes: 2, cycles: 2 /* +1 if branch succeeds, +2 if to a new page */ },
-            0xD0 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "BNE", bytes: 2, cycles: 2 /* +1 if branch succeeds, +2 if to a new page */ },
-            0x10 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "BPL", bytes: 2, cycles: 2 /* +1 if branch succeeds, +2 if to a new page */ },
-            0x50 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "BVC", bytes: 2, cycles: 2 /* +1 if branch succeeds, +2 if to a new page */ },
-            0x70 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "BVS", bytes: 2, cycles: 2 /* +1 if branch succeeds, +2 if to a new page */ },
+        0x24 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "BIT", bytes: 2, cycles: 3 },
+        0x2C => Instruction { addr_mode: AddressingMode::Absolute, name: "BIT", bytes: 3, cycles: 4 },
+    
+        0x30 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "BMI", bytes: 2, cycles: 2 /* +1 if branch succeeds, +2 if to a new page */ },
+        0xD0 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "BNE", bytes: 2, cycles: 2 /* +1 if branch succeeds, +2 if to a new page */ },
+        0x10 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "BPL", bytes: 2, cycles: 2 /* +1 if branch succeeds, +2 if to a new page */ },
+        0x50 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "BVC", bytes: 2, cycles: 2 /* +1 if branch succeeds, +2 if to a new page */ },
+        0x70 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "BVS", bytes: 2, cycles: 2 /* +1 if branch succeeds, +2 if to a new page */ },
+    
+        0x00 => Instruction {addr_mode: AddressingMode::NoneAddressing, name: "BRK", bytes: 1, cycles: 7},
+
+        0x18 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "CLC", bytes: 1, cycles: 2 },
+        0xD8 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "CLD", bytes: 1, cycles: 2 },
+        0x58 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "CLI", bytes: 1, cycles: 2 },
+        0xB8 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "CLV", bytes: 1, cycles: 2 },
+
+        0xC9 => Instruction { addr_mode: AddressingMode::Immediate, name: "CMP", bytes: 2, cycles: 2 },
+        0xC5 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "CMP", bytes: 2, cycles: 3 },
+        0xD5 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "CMP", bytes: 2, cycles: 4 },
+        0xCD => Instruction { addr_mode: AddressingMode::Absolute, name: "CMP", bytes: 3, cycles: 4 },
+        0xDD => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "CMP", bytes: 3, cycles: 4 /* +1 if page crossed */ },
+        0xD9 => Instruction { addr_mode: AddressingMode::AbsoluteY, name: "CMP", bytes: 3, cycles: 4 /* +1 if page crossed */ },
+        0xC1 => Instruction { addr_mode: AddressingMode::IndirectX, name: "CMP", bytes: 2, cycles: 6 },
+        0xD1 => Instruction { addr_mode: AddressingMode::IndirectY, name: "CMP", bytes: 2, cycles: 5 /* +1 if page crossed */ },
+
+        0xE0 => Instruction { addr_mode: AddressingMode::Immediate, name: "CPX", bytes: 2, cycles: 2 },
+        0xE4 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "CPX", bytes: 2, cycles: 3 },
+        0xEC => Instruction { addr_mode: AddressingMode::Absolute, name: "CPX", bytes: 3, cycles: 4 },
+
+        0xC0 => Instruction { addr_mode: AddressingMode::Immediate, name: "CPY", bytes: 2, cycles: 2 },
+        0xC4 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "CPY", bytes: 2, cycles: 3 },
+        0xCC => Instruction { addr_mode: AddressingMode::Absolute, name: "CPY", bytes: 3, cycles: 4 },
         
-            0x00 => Instruction {addr_mode: AddressingMode::NoneAddressing, name: "BRK", bytes: 1, cycles: 7},
-
-            0x18 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "CLC", bytes: 1, cycles: 2 },
-            0xD8 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "CLD", bytes: 1, cycles: 2 },
-            0x58 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "CLI", bytes: 1, cycles: 2 },
-            0xB8 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "CLV", bytes: 1, cycles: 2 },
-
-            0xC9 => Instruction { addr_mode: AddressingMode::Immediate, name: "CMP", bytes: 2, cycles: 2 },
-            0xC5 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "CMP", bytes: 2, cycles: 3 },
-            0xD5 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "CMP", bytes: 2, cycles: 4 },
-            0xCD => Instruction { addr_mode: AddressingMode::Absolute, name: "CMP", bytes: 3, cycles: 4 },
-            0xDD => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "CMP", bytes: 3, cycles: 4 /* +1 if page crossed */ },
-            0xD9 => Instruction { addr_mode: AddressingMode::AbsoluteY, name: "CMP", bytes: 3, cycles: 4 /* +1 if page crossed */ },
-            0xC1 => Instruction { addr_mode: AddressingMode::IndirectX, name: "CMP", bytes: 2, cycles: 6 },
-            0xD1 => Instruction { addr_mode: AddressingMode::IndirectY, name: "CMP", bytes: 2, cycles: 5 /* +1 if page crossed */ },
-
-            0xE0 => Instruction { addr_mode: AddressingMode::Immediate, name: "CPX", bytes: 2, cycles: 2 },
-            0xE4 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "CPX", bytes: 2, cycles: 3 },
-            0xEC => Instruction { addr_mode: AddressingMode::Absolute, name: "CPX", bytes: 3, cycles: 4 },
-
-            0xC0 => Instruction { addr_mode: AddressingMode::Immediate, name: "CPY", bytes: 2, cycles: 2 },
-            0xC4 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "CPY", bytes: 2, cycles: 3 },
-            0xCC => Instruction { addr_mode: AddressingMode::Absolute, name: "CPY", bytes: 3, cycles: 4 },
-            
-            0xC6 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "DEC", bytes: 2, cycles: 5 },
-            0xD6 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "DEC", bytes: 2, cycles: 6 },
-            0xCE => Instruction { addr_mode: AddressingMode::Absolute, name: "DEC", bytes: 3, cycles: 6 },
-            0xDE => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "DEC", bytes: 3, cycles: 7 },
-            
-            0xCA => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "DEX", bytes: 1, cycles: 2 },
-            0x88 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "DEY", bytes: 1, cycles: 2 },
-            
-            0x49 => Instruction { addr_mode: AddressingMode::Immediate, name: "EOR", bytes: 2, cycles: 2 },
-            0x45 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "EOR", bytes: 2, cycles: 3 },
-            0x55 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "EOR", bytes: 2, cycles: 4 },
-            0x4D => Instruction { addr_mode: AddressingMode::Absolute, name: "EOR", bytes: 3, cycles: 4 },
-            0x5D => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "EOR", bytes: 3, cycles: 4 /* +1 if page crossed */ },
-            0x59 => Instruction { addr_mode: AddressingMode::AbsoluteY, name: "EOR", bytes: 3, cycles: 4 /* +1 if page crossed */ },
-            0x41 => Instruction { addr_mode: AddressingMode::IndirectX, name: "EOR", bytes: 2, cycles: 6 },
-            0x51 => Instruction { addr_mode: AddressingMode::IndirectY, name: "EOR", bytes: 2, cycles: 5 /* +1 if page crossed */ },
-            
-            0xE6 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "INC", bytes: 2, cycles: 5 },
-            0xF6 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "INC", bytes: 2, cycles: 6 },
-            0xEE => Instruction { addr_mode: AddressingMode::Absolute, name: "INC", bytes: 3, cycles: 6 },
-            0xFE => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "INC", bytes: 3, cycles: 7 },
-            
-            0xE8 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "INX", bytes: 1, cycles: 2 },
-            0xC8 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "INY", bytes: 1, cycles: 2 },
-            
-            0x4C => Instruction { addr_mode: AddressingMode::Absolute, name: "JMP", bytes: 3, cycles: 3 },
-            0x6C => Instruction { addr_mode: AddressingMode::NoneAddressing , name: "JMP_INDIRECT", bytes: 3, cycles: 5 },
-            
-            0x20 => Instruction { addr_mode: AddressingMode::Absolute , name: "JSR", bytes: 3, cycles: 6 },
-            
-            0xA9 => Instruction { addr_mode: AddressingMode::Immediate, name: "LDA", bytes: 2, cycles: 2 },
-            0xA5 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "LDA", bytes: 2, cycles: 3 },
-            0xB5 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "LDA", bytes: 2, cycles: 4 },
-            0xAD => Instruction { addr_mode: AddressingMode::Absolute, name: "LDA", bytes: 3, cycles: 4 },
-            0xBD => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "LDA", bytes: 3, cycles: 4 /* +1 if page crossed */ },
-            0xB9 => Instruction { addr_mode: AddressingMode::AbsoluteY, name: "LDA", bytes: 3, cycles: 4 /* +1 if page crossed */ },
-            0xA1 => Instruction { addr_mode: AddressingMode::IndirectX, name: "LDA", bytes: 2, cycles: 6 },
-            0xB1 => Instruction { addr_mode: AddressingMode::IndirectY, name: "LDA", bytes: 2, cycles: 5 /* +1 if page crossed */ },
-
-            0xA2 => Instruction { addr_mode: AddressingMode::Immediate, name: "LDX", bytes: 2, cycles: 2 },
-            0xA6 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "LDX", bytes: 2, cycles: 3 },
-            0xB6 => Instruction { addr_mode: AddressingMode::ZeroPageY, name: "LDX", bytes: 2, cycles: 4 },
-            0xAE => Instruction { addr_mode: AddressingMode::Absolute, name: "LDX", bytes: 3, cycles: 4 },
-            0xBE => Instruction { addr_mode: AddressingMode::AbsoluteY, name: "LDX", bytes: 3, cycles: 4 /* +1 if page crossed */ },
-
-            0xA0 => Instruction { addr_mode: AddressingMode::Immediate, name: "LDY", bytes: 2, cycles: 2 },
-            0xA4 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "LDY", bytes: 2, cycles: 3 },
-            0xB4 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "LDY", bytes: 2, cycles: 4 },
-            0xAC => Instruction { addr_mode: AddressingMode::Absolute, name: "LDY", bytes: 3, cycles: 4 },
-            0xBC => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "LDY", bytes: 3, cycles: 4 /* +1 if page crossed */ },
-
-            0x4A => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "LSR_ACC", bytes: 1, cycles: 2 },
-            0x46 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "LSR", bytes: 2, cycles: 5 },
-            0x56 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "LSR", bytes: 2, cycles: 6 },
-            0x4E => Instruction { addr_mode: AddressingMode::Absolute, name: "LSR", bytes: 3, cycles: 6 },
-            0x5E => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "LSR", bytes: 3, cycles: 7 },
-
-            0xEA => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "NOP", bytes: 1 , cycles: 2 },
-
-            0x09 => Instruction { addr_mode: AddressingMode::Immediate, name: "ORA", bytes: 2, cycles: 2 },
-            0x05 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "ORA", bytes: 2, cycles: 3 },
-            0x15 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "ORA", bytes: 2, cycles: 4 },
-            0x0D => Instruction { addr_mode: AddressingMode::Absolute, name: "ORA", bytes: 3, cycles: 4 },
-            0x1D => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "ORA", bytes: 3, cycles: 4 /* +1 if page crossed */ },
-            0x19 => Instruction { addr_mode: AddressingMode::AbsoluteY, name: "ORA", bytes: 3, cycles: 4 /* +1 if page crossed */ },
-            0x01 => Instruction { addr_mode: AddressingMode::IndirectX, name: "ORA", bytes: 2, cycles: 6 },
-            0x11 => Instruction { addr_mode: AddressingMode::IndirectY, name: "ORA", bytes: 2, cycles: 5 /* +1 if page crossed */ },
-
-            0x48 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "PHA", bytes: 1, cycles: 3 },
-            0x08 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "PHP", bytes: 1, cycles: 3 },
-            0x68 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "PLA", bytes: 1, cycles: 4 },
-            0x28 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "PLP", bytes: 1, cycles: 4 },
-            
-            0x2A => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "ROL", bytes: 1, cycles: 2 },
-            0x26 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "ROL", bytes: 2, cycles: 5 },
-            0x36 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "ROL", bytes: 2, cycles: 6 },
-            0x2E => Instruction { addr_mode: AddressingMode::Absolute, name: "ROL", bytes: 3, cycles: 6 },
-            0x3E => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "ROL", bytes: 3, cycles: 7 },
-
-            0x6A => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "ROR", bytes: 1, cycles: 2 },
-            0x66 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "ROR", bytes: 2, cycles: 5 },
-            0x76 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "ROR", bytes: 2, cycles: 6 },
-            0x6E => Instruction { addr_mode: AddressingMode::Absolute, name: "ROR", bytes: 3, cycles: 6 },
-            0x7E => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "ROR", bytes: 3, cycles: 7 },
-
-            0x40 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "RTI", bytes: 1, cycles: 6 },
-            0x60 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "RTS", bytes: 1, cycles: 6 },
-
-            0xE9 => Instruction { addr_mode: AddressingMode::Immediate, name: "SBC", bytes: 2, cycles: 2 },
-            0xE5 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "SBC", bytes: 2, cycles: 3 },
-            0xF5 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "SBC", bytes: 2, cycles: 4 },
-            0xED => Instruction { addr_mode: AddressingMode::Absolute, name: "SBC", bytes: 3, cycles: 4 },
-            0xFD => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "SBC", bytes: 3, cycles: 4 /* +1 if page crossed */ },
-            0xF9 => Instruction { addr_mode: AddressingMode::AbsoluteY, name: "SBC", bytes: 3, cycles: 4 /* +1 if page crossed */ },
-            0xE1 => Instruction { addr_mode: AddressingMode::IndirectX, name: "SBC", bytes: 2, cycles: 6 },
-            0xF1 => Instruction { addr_mode: AddressingMode::IndirectY, name: "SBC", bytes: 2, cycles: 5 /* +1 if page crossed */ },
-
-            0x38 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "SEC", bytes: 1, cycles: 2 },
-            0xF8 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "SED", bytes: 1, cycles: 2 },
-            0x78 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "SEI", bytes: 1, cycles: 2 },
-
-            0x85 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "STA", bytes: 2, cycles: 3 },
-            0x95 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "STA", bytes: 2, cycles: 4 },
-            0x8D => Instruction { addr_mode: AddressingMode::Absolute, name: "STA", bytes: 3, cycles: 4 },
-            0x9D => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "STA", bytes: 3, cycles: 5 },
-            0x99 => Instruction { addr_mode: AddressingMode::AbsoluteY, name: "STA", bytes: 3, cycles: 5 },
-            0x81 => Instruction { addr_mode: AddressingMode::IndirectX, name: "STA", bytes: 2, cycles: 6 },
-            0x91 => Instruction { addr_mode: AddressingMode::IndirectY, name: "STA", bytes: 2, cycles: 6 },
-
-            0x86 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "STX", bytes: 2, cycles: 3 },
-            0x96 => Instruction { addr_mode: AddressingMode::ZeroPageY, name: "STX", bytes: 2, cycles: 4 },
-            0x8E => Instruction { addr_mode: AddressingMode::Absolute, name: "STX", bytes: 3, cycles: 4 },
-
-            0x84 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "STY", bytes: 2, cycles: 3 },
-            0x94 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "STY", bytes: 2, cycles: 4 },
-            0x8C => Instruction { addr_mode: AddressingMode::Absolute, name: "STY", bytes: 3, cycles: 4 },
-
-            0xAA => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "TAX", bytes: 1, cycles: 2 },
-            0xA8 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "TAY", bytes: 1, cycles: 2 },
-            0xBA => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "TSX", bytes: 1, cycles: 2 },
-            0x8A => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "TXA", bytes: 1, cycles: 2 },
-            0x9A => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "TXS", bytes: 1, cycles: 2 },
-            0x98 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "TYA", bytes: 1, cycles: 2 },
-            
-            // Unofficial Opcodes
-
-            0xc7 => Instruction { name: "*DCP", bytes: 2, cycles: 5, addr_mode: AddressingMode::ZeroPage },
-            0xd7 => Instruction { name: "*DCP", bytes: 2, cycles: 6, addr_mode: AddressingMode::ZeroPageX },
-            0xCF => Instruction { name: "*DCP", bytes: 3, cycles: 6, addr_mode: AddressingMode::Absolute },
-            0xDF => Instruction { name: "*DCP", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteX },
-            0xDB => Instruction { name: "*DCP", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteY },
-            0xd3 => Instruction { name: "*DCP", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectY },
-            0xc3 => Instruction { name: "*DCP", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectX },
-
-
-            0x27 => Instruction { name: "*RLA", bytes: 2, cycles: 5, addr_mode: AddressingMode::ZeroPage },
-            0x37 => Instruction { name: "*RLA", bytes: 2, cycles: 6, addr_mode: AddressingMode::ZeroPageX },
-            0x2F => Instruction { name: "*RLA", bytes: 3, cycles: 6, addr_mode: AddressingMode::Absolute },
-            0x3F => Instruction { name: "*RLA", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteX },
-            0x3b => Instruction { name: "*RLA", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteY },
-            0x33 => Instruction { name: "*RLA", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectY },
-            0x23 => Instruction { name: "*RLA", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectX },
-
-            0x07 => Instruction { name: "*SLO", bytes: 2, cycles: 5, addr_mode: AddressingMode::ZeroPage },
-            0x17 => Instruction { name: "*SLO", bytes: 2, cycles: 6, addr_mode: AddressingMode::ZeroPageX },
-            0x0F => Instruction { name: "*SLO", bytes: 3, cycles: 6, addr_mode: AddressingMode::Absolute },
-            0x1f => Instruction { name: "*SLO", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteX },
-            0x1b => Instruction { name: "*SLO", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteY },
-            0x03 => Instruction { name: "*SLO", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectX },
-            0x13 => Instruction { name: "*SLO", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectY },
-
-            0x47 => Instruction { name: "*SRE", bytes: 2, cycles: 5, addr_mode: AddressingMode::ZeroPage },
-            0x57 => Instruction { name: "*SRE", bytes: 2, cycles: 6, addr_mode: AddressingMode::ZeroPageX },
-            0x4F => Instruction { name: "*SRE", bytes: 3, cycles: 6, addr_mode: AddressingMode::Absolute },
-            0x5f => Instruction { name: "*SRE", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteX },
-            0x5b => Instruction { name: "*SRE", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteY },
-            0x43 => Instruction { name: "*SRE", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectX },
-            0x53 => Instruction { name: "*SRE", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectY },
-
-
-            0x80 => Instruction { name: "*NOP", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
-            0x82 => Instruction { name: "*NOP", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
-            0x89 => Instruction { name: "*NOP", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
-            0xc2 => Instruction { name: "*NOP", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
-            0xe2 => Instruction { name: "*NOP", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
-
-
-            0xCB => Instruction { name: "*AXS", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
-
-            0x6B => Instruction { name: "*ARR", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
-
-            0xeb => Instruction { name: "*SBC", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
-
-            0x0b => Instruction { name: "*ANC", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
-            0x2b => Instruction { name: "*ANC", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
-
-            0x4b => Instruction { name: "*ALR", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
-            // 0xCB => Instruction { name: "IGN", 3,4 /* or 5*/, AddressingMode::AbsoluteX),
-
-            0x04 => Instruction { name: "*NOP", bytes: 2, cycles: 3, addr_mode: AddressingMode::ZeroPage },
-            0x44 => Instruction { name: "*NOP", bytes: 2, cycles: 3, addr_mode: AddressingMode::ZeroPage },
-            0x64 => Instruction { name: "*NOP", bytes: 2, cycles: 3, addr_mode: AddressingMode::ZeroPage },
-            0x14 => Instruction { name: "*NOP", bytes: 2, cycles: 4, addr_mode: AddressingMode::ZeroPageX },
-            0x34 => Instruction { name: "*NOP", bytes: 2, cycles: 4, addr_mode: AddressingMode::ZeroPageX },
-            0x54 => Instruction { name: "*NOP", bytes: 2, cycles: 4, addr_mode: AddressingMode::ZeroPageX },
-            0x74 => Instruction { name: "*NOP", bytes: 2, cycles: 4, addr_mode: AddressingMode::ZeroPageX },
-            0xd4 => Instruction { name: "*NOP", bytes: 2, cycles: 4, addr_mode: AddressingMode::ZeroPageX },
-            0xf4 => Instruction { name: "*NOP", bytes: 2, cycles: 4, addr_mode: AddressingMode::ZeroPageX },
-            0x0c => Instruction { name: "*NOP", bytes: 3, cycles: 4, addr_mode: AddressingMode::Absolute },
-            0x1c => Instruction { name: "*NOP", bytes: 3, cycles: 4 /*or 5*/, addr_mode: AddressingMode::AbsoluteX },
-            0x3c => Instruction { name: "*NOP", bytes: 3, cycles: 4 /*or 5*/, addr_mode: AddressingMode::AbsoluteX },
-            0x5c => Instruction { name: "*NOP", bytes: 3, cycles: 4 /*or 5*/, addr_mode: AddressingMode::AbsoluteX },
-            0x7c => Instruction { name: "*NOP", bytes: 3, cycles: 4 /*or 5*/, addr_mode: AddressingMode::AbsoluteX },
-            0xdc => Instruction { name: "*NOP", bytes: 3, cycles: 4 /* or 5*/, addr_mode: AddressingMode::AbsoluteX },
-            0xfc => Instruction { name: "*NOP", bytes: 3, cycles: 4 /* or 5*/, addr_mode: AddressingMode::AbsoluteX },
-
-            0x67 => Instruction { name: "*RRA", bytes: 2, cycles: 5, addr_mode: AddressingMode::ZeroPage },
-            0x77 => Instruction { name: "*RRA", bytes: 2, cycles: 6, addr_mode: AddressingMode::ZeroPageX },
-            0x6f => Instruction { name: "*RRA", bytes: 3, cycles: 6, addr_mode: AddressingMode::Absolute },
-            0x7f => Instruction { name: "*RRA", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteX },
-            0x7b => Instruction { name: "*RRA", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteY },
-            0x63 => Instruction { name: "*RRA", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectX },
-            0x73 => Instruction { name: "*RRA", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectY },
-
-
-            0xe7 => Instruction { name: "*ISB", bytes: 2, cycles: 5, addr_mode: AddressingMode::ZeroPage },
-            0xf7 => Instruction { name: "*ISB", bytes: 2, cycles: 6, addr_mode: AddressingMode::ZeroPageX },
-            0xef => Instruction { name: "*ISB", bytes: 3, cycles: 6, addr_mode: AddressingMode::Absolute },
-            0xff => Instruction { name: "*ISB", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteX },
-            0xfb => Instruction { name: "*ISB", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteY },
-            0xe3 => Instruction { name: "*ISB", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectX },
-            0xf3 => Instruction { name: "*ISB", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectY },
-
-            0x12 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
-            0x22 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
-            0x02 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
-            0x32 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
-            0x42 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
-            0x52 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
-            0x62 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
-            0x72 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
-            0x92 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
-            0xb2 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
-            0xd2 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
-            0xf2 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
-
-            0x1a => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
-            0x3a => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
-            0x5a => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
-            0x7a => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
-            0xda => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
-            // 0xea => Instruction { name: "NOP", 1,2, AddressingMode::NoneAddressing),
-            0xfa => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
-
-            0xab => Instruction { name: "*LXA", bytes: 2, cycles: 3, addr_mode: AddressingMode::Immediate }, //todo: highly unstable and not used
-            //http://visual6502.org/wiki/index.php?title=6502_Opcode_8B_%28XAA,_ANE%29
-            0x8b => Instruction { name: "*XAA", bytes: 2, cycles: 3, addr_mode: AddressingMode::Immediate }, //todo: highly unstable and not used
-            0xbb => Instruction { name: "*LAS", bytes: 3, cycles: 2, addr_mode: AddressingMode::AbsoluteY }, //todo: highly unstable and not used
-            0x9b => Instruction { name: "*TAS", bytes: 3, cycles: 2, addr_mode: AddressingMode::AbsoluteY }, //todo: highly unstable and not used
-            0x93 => Instruction { name: "*AHX", bytes: 2, cycles: 8 /* guess */, addr_mode: AddressingMode::IndirectY }, //todo: highly unstable and not used
-            0x9f => Instruction { name: "*AHX", bytes: 3, cycles: 4 /* or 5*/ /* guess */, addr_mode: AddressingMode::AbsoluteY }, //todo: highly unstable and not used
-            0x9e => Instruction { name: "*SHX", bytes: 3, cycles: 4 /* or 5*/ /* guess */, addr_mode: AddressingMode::AbsoluteY }, //todo: highly unstable and not used
-            0x9c => Instruction { name: "*SHY", bytes: 3, cycles: 4 /* or 5*/ /* guess */, addr_mode: AddressingMode::AbsoluteX }, //todo: highly unstable and not used
-
-            0xa7 => Instruction { name: "*LAX", bytes: 2, cycles: 3, addr_mode: AddressingMode::ZeroPage },
-            0xb7 => Instruction { name: "*LAX", bytes: 2, cycles: 4, addr_mode: AddressingMode::ZeroPageY },
-            0xaf => Instruction { name: "*LAX", bytes: 3, cycles: 4, addr_mode: AddressingMode::Absolute },
-            0xbf => Instruction { name: "*LAX", bytes: 3, cycles: 4, addr_mode: AddressingMode::AbsoluteY },
-            0xa3 => Instruction { name: "*LAX", bytes: 2, cycles: 6, addr_mode: AddressingMode::IndirectX },
-            0xb3 => Instruction { name: "*LAX", bytes: 2, cycles: 5, addr_mode: AddressingMode::IndirectY },
-
-            0x87 => Instruction { name: "*SAX", bytes: 2, cycles: 3, addr_mode: AddressingMode::ZeroPage },
-            0x97 => Instruction { name: "*SAX", bytes: 2, cycles: 4, addr_mode: AddressingMode::ZeroPageY },
-            0x8f => Instruction { name: "*SAX", bytes: 3, cycles: 4, addr_mode: AddressingMode::Absolute },
-            0x83 => Instruction { name: "*SAX", bytes: 2, cycles: 6, addr_mode: AddressingMode::IndirectX },
-        }
+        0xC6 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "DEC", bytes: 2, cycles: 5 },
+        0xD6 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "DEC", bytes: 2, cycles: 6 },
+        0xCE => Instruction { addr_mode: AddressingMode::Absolute, name: "DEC", bytes: 3, cycles: 6 },
+        0xDE => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "DEC", bytes: 3, cycles: 7 },
+        
+        0xCA => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "DEX", bytes: 1, cycles: 2 },
+        0x88 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "DEY", bytes: 1, cycles: 2 },
+        
+        0x49 => Instruction { addr_mode: AddressingMode::Immediate, name: "EOR", bytes: 2, cycles: 2 },
+        0x45 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "EOR", bytes: 2, cycles: 3 },
+        0x55 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "EOR", bytes: 2, cycles: 4 },
+        0x4D => Instruction { addr_mode: AddressingMode::Absolute, name: "EOR", bytes: 3, cycles: 4 },
+        0x5D => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "EOR", bytes: 3, cycles: 4 /* +1 if page crossed */ },
+        0x59 => Instruction { addr_mode: AddressingMode::AbsoluteY, name: "EOR", bytes: 3, cycles: 4 /* +1 if page crossed */ },
+        0x41 => Instruction { addr_mode: AddressingMode::IndirectX, name: "EOR", bytes: 2, cycles: 6 },
+        0x51 => Instruction { addr_mode: AddressingMode::IndirectY, name: "EOR", bytes: 2, cycles: 5 /* +1 if page crossed */ },
+        
+        0xE6 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "INC", bytes: 2, cycles: 5 },
+        0xF6 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "INC", bytes: 2, cycles: 6 },
+        0xEE => Instruction { addr_mode: AddressingMode::Absolute, name: "INC", bytes: 3, cycles: 6 },
+        0xFE => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "INC", bytes: 3, cycles: 7 },
+        
+        0xE8 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "INX", bytes: 1, cycles: 2 },
+        0xC8 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "INY", bytes: 1, cycles: 2 },
+        
+        0x4C => Instruction { addr_mode: AddressingMode::Absolute, name: "JMP", bytes: 3, cycles: 3 },
+        0x6C => Instruction { addr_mode: AddressingMode::NoneAddressing , name: "JMP_INDIRECT", bytes: 3, cycles: 5 },
+        
+        0x20 => Instruction { addr_mode: AddressingMode::Absolute , name: "JSR", bytes: 3, cycles: 6 },
+        
+        0xA9 => Instruction { addr_mode: AddressingMode::Immediate, name: "LDA", bytes: 2, cycles: 2 },
+        0xA5 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "LDA", bytes: 2, cycles: 3 },
+        0xB5 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "LDA", bytes: 2, cycles: 4 },
+        0xAD => Instruction { addr_mode: AddressingMode::Absolute, name: "LDA", bytes: 3, cycles: 4 },
+        0xBD => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "LDA", bytes: 3, cycles: 4 /* +1 if page crossed */ },
+        0xB9 => Instruction { addr_mode: AddressingMode::AbsoluteY, name: "LDA", bytes: 3, cycles: 4 /* +1 if page crossed */ },
+        0xA1 => Instruction { addr_mode: AddressingMode::IndirectX, name: "LDA", bytes: 2, cycles: 6 },
+        0xB1 => Instruction { addr_mode: AddressingMode::IndirectY, name: "LDA", bytes: 2, cycles: 5 /* +1 if page crossed */ },
+
+        0xA2 => Instruction { addr_mode: AddressingMode::Immediate, name: "LDX", bytes: 2, cycles: 2 },
+        0xA6 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "LDX", bytes: 2, cycles: 3 },
+        0xB6 => Instruction { addr_mode: AddressingMode::ZeroPageY, name: "LDX", bytes: 2, cycles: 4 },
+        0xAE => Instruction { addr_mode: AddressingMode::Absolute, name: "LDX", bytes: 3, cycles: 4 },
+        0xBE => Instruction { addr_mode: AddressingMode::AbsoluteY, name: "LDX", bytes: 3, cycles: 4 /* +1 if page crossed */ },
+
+        0xA0 => Instruction { addr_mode: AddressingMode::Immediate, name: "LDY", bytes: 2, cycles: 2 },
+        0xA4 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "LDY", bytes: 2, cycles: 3 },
+        0xB4 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "LDY", bytes: 2, cycles: 4 },
+        0xAC => Instruction { addr_mode: AddressingMode::Absolute, name: "LDY", bytes: 3, cycles: 4 },
+        0xBC => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "LDY", bytes: 3, cycles: 4 /* +1 if page crossed */ },
+
+        0x4A => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "LSR_ACC", bytes: 1, cycles: 2 },
+        0x46 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "LSR", bytes: 2, cycles: 5 },
+        0x56 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "LSR", bytes: 2, cycles: 6 },
+        0x4E => Instruction { addr_mode: AddressingMode::Absolute, name: "LSR", bytes: 3, cycles: 6 },
+        0x5E => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "LSR", bytes: 3, cycles: 7 },
+
+        0xEA => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "NOP", bytes: 1 , cycles: 2 },
+
+        0x09 => Instruction { addr_mode: AddressingMode::Immediate, name: "ORA", bytes: 2, cycles: 2 },
+        0x05 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "ORA", bytes: 2, cycles: 3 },
+        0x15 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "ORA", bytes: 2, cycles: 4 },
+        0x0D => Instruction { addr_mode: AddressingMode::Absolute, name: "ORA", bytes: 3, cycles: 4 },
+        0x1D => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "ORA", bytes: 3, cycles: 4 /* +1 if page crossed */ },
+        0x19 => Instruction { addr_mode: AddressingMode::AbsoluteY, name: "ORA", bytes: 3, cycles: 4 /* +1 if page crossed */ },
+        0x01 => Instruction { addr_mode: AddressingMode::IndirectX, name: "ORA", bytes: 2, cycles: 6 },
+        0x11 => Instruction { addr_mode: AddressingMode::IndirectY, name: "ORA", bytes: 2, cycles: 5 /* +1 if page crossed */ },
+
+        0x48 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "PHA", bytes: 1, cycles: 3 },
+        0x08 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "PHP", bytes: 1, cycles: 3 },
+        0x68 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "PLA", bytes: 1, cycles: 4 },
+        0x28 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "PLP", bytes: 1, cycles: 4 },
+        
+        0x2A => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "ROL", bytes: 1, cycles: 2 },
+        0x26 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "ROL", bytes: 2, cycles: 5 },
+        0x36 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "ROL", bytes: 2, cycles: 6 },
+        0x2E => Instruction { addr_mode: AddressingMode::Absolute, name: "ROL", bytes: 3, cycles: 6 },
+        0x3E => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "ROL", bytes: 3, cycles: 7 },
+
+        0x6A => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "ROR", bytes: 1, cycles: 2 },
+        0x66 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "ROR", bytes: 2, cycles: 5 },
+        0x76 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "ROR", bytes: 2, cycles: 6 },
+        0x6E => Instruction { addr_mode: AddressingMode::Absolute, name: "ROR", bytes: 3, cycles: 6 },
+        0x7E => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "ROR", bytes: 3, cycles: 7 },
+
+        0x40 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "RTI", bytes: 1, cycles: 6 },
+        0x60 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "RTS", bytes: 1, cycles: 6 },
+
+        0xE9 => Instruction { addr_mode: AddressingMode::Immediate, name: "SBC", bytes: 2, cycles: 2 },
+        0xE5 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "SBC", bytes: 2, cycles: 3 },
+        0xF5 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "SBC", bytes: 2, cycles: 4 },
+        0xED => Instruction { addr_mode: AddressingMode::Absolute, name: "SBC", bytes: 3, cycles: 4 },
+        0xFD => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "SBC", bytes: 3, cycles: 4 /* +1 if page crossed */ },
+        0xF9 => Instruction { addr_mode: AddressingMode::AbsoluteY, name: "SBC", bytes: 3, cycles: 4 /* +1 if page crossed */ },
+        0xE1 => Instruction { addr_mode: AddressingMode::IndirectX, name: "SBC", bytes: 2, cycles: 6 },
+        0xF1 => Instruction { addr_mode: AddressingMode::IndirectY, name: "SBC", bytes: 2, cycles: 5 /* +1 if page crossed */ },
+
+        0x38 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "SEC", bytes: 1, cycles: 2 },
+        0xF8 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "SED", bytes: 1, cycles: 2 },
+        0x78 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "SEI", bytes: 1, cycles: 2 },
+
+        0x85 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "STA", bytes: 2, cycles: 3 },
+        0x95 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "STA", bytes: 2, cycles: 4 },
+        0x8D => Instruction { addr_mode: AddressingMode::Absolute, name: "STA", bytes: 3, cycles: 4 },
+        0x9D => Instruction { addr_mode: AddressingMode::AbsoluteX, name: "STA", bytes: 3, cycles: 5 },
+        0x99 => Instruction { addr_mode: AddressingMode::AbsoluteY, name: "STA", bytes: 3, cycles: 5 },
+        0x81 => Instruction { addr_mode: AddressingMode::IndirectX, name: "STA", bytes: 2, cycles: 6 },
+        0x91 => Instruction { addr_mode: AddressingMode::IndirectY, name: "STA", bytes: 2, cycles: 6 },
+
+        0x86 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "STX", bytes: 2, cycles: 3 },
+        0x96 => Instruction { addr_mode: AddressingMode::ZeroPageY, name: "STX", bytes: 2, cycles: 4 },
+        0x8E => Instruction { addr_mode: AddressingMode::Absolute, name: "STX", bytes: 3, cycles: 4 },
+
+        0x84 => Instruction { addr_mode: AddressingMode::ZeroPage, name: "STY", bytes: 2, cycles: 3 },
+        0x94 => Instruction { addr_mode: AddressingMode::ZeroPageX, name: "STY", bytes: 2, cycles: 4 },
+        0x8C => Instruction { addr_mode: AddressingMode::Absolute, name: "STY", bytes: 3, cycles: 4 },
+
+        0xAA => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "TAX", bytes: 1, cycles: 2 },
+        0xA8 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "TAY", bytes: 1, cycles: 2 },
+        0xBA => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "TSX", bytes: 1, cycles: 2 },
+        0x8A => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "TXA", bytes: 1, cycles: 2 },
+        0x9A => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "TXS", bytes: 1, cycles: 2 },
+        0x98 => Instruction { addr_mode: AddressingMode::NoneAddressing, name: "TYA", bytes: 1, cycles: 2 },
+        
+        // Unofficial Opcodes
+
+        0xc7 => Instruction { name: "*DCP", bytes: 2, cycles: 5, addr_mode: AddressingMode::ZeroPage },
+        0xd7 => Instruction { name: "*DCP", bytes: 2, cycles: 6, addr_mode: AddressingMode::ZeroPageX },
+        0xCF => Instruction { name: "*DCP", bytes: 3, cycles: 6, addr_mode: AddressingMode::Absolute },
+        0xDF => Instruction { name: "*DCP", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteX },
+        0xDB => Instruction { name: "*DCP", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteY },
+        0xd3 => Instruction { name: "*DCP", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectY },
+        0xc3 => Instruction { name: "*DCP", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectX },
+
+
+        0x27 => Instruction { name: "*RLA", bytes: 2, cycles: 5, addr_mode: AddressingMode::ZeroPage },
+        0x37 => Instruction { name: "*RLA", bytes: 2, cycles: 6, addr_mode: AddressingMode::ZeroPageX },
+        0x2F => Instruction { name: "*RLA", bytes: 3, cycles: 6, addr_mode: AddressingMode::Absolute },
+        0x3F => Instruction { name: "*RLA", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteX },
+        0x3b => Instruction { name: "*RLA", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteY },
+        0x33 => Instruction { name: "*RLA", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectY },
+        0x23 => Instruction { name: "*RLA", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectX },
+
+        0x07 => Instruction { name: "*SLO", bytes: 2, cycles: 5, addr_mode: AddressingMode::ZeroPage },
+        0x17 => Instruction { name: "*SLO", bytes: 2, cycles: 6, addr_mode: AddressingMode::ZeroPageX },
+        0x0F => Instruction { name: "*SLO", bytes: 3, cycles: 6, addr_mode: AddressingMode::Absolute },
+        0x1f => Instruction { name: "*SLO", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteX },
+        0x1b => Instruction { name: "*SLO", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteY },
+        0x03 => Instruction { name: "*SLO", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectX },
+        0x13 => Instruction { name: "*SLO", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectY },
+
+        0x47 => Instruction { name: "*SRE", bytes: 2, cycles: 5, addr_mode: AddressingMode::ZeroPage },
+        0x57 => Instruction { name: "*SRE", bytes: 2, cycles: 6, addr_mode: AddressingMode::ZeroPageX },
+        0x4F => Instruction { name: "*SRE", bytes: 3, cycles: 6, addr_mode: AddressingMode::Absolute },
+        0x5f => Instruction { name: "*SRE", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteX },
+        0x5b => Instruction { name: "*SRE", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteY },
+        0x43 => Instruction { name: "*SRE", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectX },
+        0x53 => Instruction { name: "*SRE", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectY },
+
+
+        0x80 => Instruction { name: "*NOP", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
+        0x82 => Instruction { name: "*NOP", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
+        0x89 => Instruction { name: "*NOP", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
+        0xc2 => Instruction { name: "*NOP", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
+        0xe2 => Instruction { name: "*NOP", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
+
+
+        0xCB => Instruction { name: "*AXS", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
+
+        0x6B => Instruction { name: "*ARR", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
+
+        0xeb => Instruction { name: "*SBC", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
+
+        0x0b => Instruction { name: "*ANC", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
+        0x2b => Instruction { name: "*ANC", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
+
+        0x4b => Instruction { name: "*ALR", bytes: 2, cycles: 2, addr_mode: AddressingMode::Immediate },
+        // 0xCB => Instruction { name: "IGN", 3,4 /* or 5*/, AddressingMode::AbsoluteX),
+
+        0x04 => Instruction { name: "*NOP", bytes: 2, cycles: 3, addr_mode: AddressingMode::ZeroPage },
+        0x44 => Instruction { name: "*NOP", bytes: 2, cycles: 3, addr_mode: AddressingMode::ZeroPage },
+        0x64 => Instruction { name: "*NOP", bytes: 2, cycles: 3, addr_mode: AddressingMode::ZeroPage },
+        0x14 => Instruction { name: "*NOP", bytes: 2, cycles: 4, addr_mode: AddressingMode::ZeroPageX },
+        0x34 => Instruction { name: "*NOP", bytes: 2, cycles: 4, addr_mode: AddressingMode::ZeroPageX },
+        0x54 => Instruction { name: "*NOP", bytes: 2, cycles: 4, addr_mode: AddressingMode::ZeroPageX },
+        0x74 => Instruction { name: "*NOP", bytes: 2, cycles: 4, addr_mode: AddressingMode::ZeroPageX },
+        0xd4 => Instruction { name: "*NOP", bytes: 2, cycles: 4, addr_mode: AddressingMode::ZeroPageX },
+        0xf4 => Instruction { name: "*NOP", bytes: 2, cycles: 4, addr_mode: AddressingMode::ZeroPageX },
+        0x0c => Instruction { name: "*NOP", bytes: 3, cycles: 4, addr_mode: AddressingMode::Absolute },
+        0x1c => Instruction { name: "*NOP", bytes: 3, cycles: 4 /*or 5*/, addr_mode: AddressingMode::AbsoluteX },
+        0x3c => Instruction { name: "*NOP", bytes: 3, cycles: 4 /*or 5*/, addr_mode: AddressingMode::AbsoluteX },
+        0x5c => Instruction { name: "*NOP", bytes: 3, cycles: 4 /*or 5*/, addr_mode: AddressingMode::AbsoluteX },
+        0x7c => Instruction { name: "*NOP", bytes: 3, cycles: 4 /*or 5*/, addr_mode: AddressingMode::AbsoluteX },
+        0xdc => Instruction { name: "*NOP", bytes: 3, cycles: 4 /* or 5*/, addr_mode: AddressingMode::AbsoluteX },
+        0xfc => Instruction { name: "*NOP", bytes: 3, cycles: 4 /* or 5*/, addr_mode: AddressingMode::AbsoluteX },
+
+        0x67 => Instruction { name: "*RRA", bytes: 2, cycles: 5, addr_mode: AddressingMode::ZeroPage },
+        0x77 => Instruction { name: "*RRA", bytes: 2, cycles: 6, addr_mode: AddressingMode::ZeroPageX },
+        0x6f => Instruction { name: "*RRA", bytes: 3, cycles: 6, addr_mode: AddressingMode::Absolute },
+        0x7f => Instruction { name: "*RRA", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteX },
+        0x7b => Instruction { name: "*RRA", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteY },
+        0x63 => Instruction { name: "*RRA", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectX },
+        0x73 => Instruction { name: "*RRA", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectY },
+
+
+        0xe7 => Instruction { name: "*ISB", bytes: 2, cycles: 5, addr_mode: AddressingMode::ZeroPage },
+        0xf7 => Instruction { name: "*ISB", bytes: 2, cycles: 6, addr_mode: AddressingMode::ZeroPageX },
+        0xef => Instruction { name: "*ISB", bytes: 3, cycles: 6, addr_mode: AddressingMode::Absolute },
+        0xff => Instruction { name: "*ISB", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteX },
+        0xfb => Instruction { name: "*ISB", bytes: 3, cycles: 7, addr_mode: AddressingMode::AbsoluteY },
+        0xe3 => Instruction { name: "*ISB", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectX },
+        0xf3 => Instruction { name: "*ISB", bytes: 2, cycles: 8, addr_mode: AddressingMode::IndirectY },
+
+        0x12 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
+        0x22 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
+        0x02 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
+        0x32 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
+        0x42 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
+        0x52 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
+        0x62 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
+        0x72 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
+        0x92 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
+        0xb2 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
+        0xd2 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
+        0xf2 => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
+
+        0x1a => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
+        0x3a => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
+        0x5a => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
+        0x7a => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
+        0xda => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
+        // 0xea => Instruction { name: "NOP", 1,2, AddressingMode::NoneAddressing),
+        0xfa => Instruction { name: "*NOP", bytes: 1, cycles: 2, addr_mode: AddressingMode::NoneAddressing },
+
+        0xab => Instruction { name: "*LXA", bytes: 2, cycles: 3, addr_mode: AddressingMode::Immediate }, //todo: highly unstable and not used
+        //http://visual6502.org/wiki/index.php?title=6502_Opcode_8B_%28XAA,_ANE%29
+        0x8b => Instruction { name: "*XAA", bytes: 2, cycles: 3, addr_mode: AddressingMode::Immediate }, //todo: highly unstable and not used
+        0xbb => Instruction { name: "*LAS", bytes: 3, cycles: 2, addr_mode: AddressingMode::AbsoluteY }, //todo: highly unstable and not used
+        0x9b => Instruction { name: "*TAS", bytes: 3, cycles: 2, addr_mode: AddressingMode::AbsoluteY }, //todo: highly unstable and not used
+        0x93 => Instruction { name: "*AHX", bytes: 2, cycles: 8 /* guess */, addr_mode: AddressingMode::IndirectY }, //todo: highly unstable and not used
+        0x9f => Instruction { name: "*AHX", bytes: 3, cycles: 4 /* or 5*/ /* guess */, addr_mode: AddressingMode::AbsoluteY }, //todo: highly unstable and not used
+        0x9e => Instruction { name: "*SHX", bytes: 3, cycles: 4 /* or 5*/ /* guess */, addr_mode: AddressingMode::AbsoluteY }, //todo: highly unstable and not used
+        0x9c => Instruction { name: "*SHY", bytes: 3, cycles: 4 /* or 5*/ /* guess */, addr_mode: AddressingMode::AbsoluteX }, //todo: highly unstable and not used
+
+        0xa7 => Instruction { name: "*LAX", bytes: 2, cycles: 3, addr_mode: AddressingMode::ZeroPage },
+        0xb7 => Instruction { name: "*LAX", bytes: 2, cycles: 4, addr_mode: AddressingMode::ZeroPageY },
+        0xaf => Instruction { name: "*LAX", bytes: 3, cycles: 4, addr_mode: AddressingMode::Absolute },
+        0xbf => Instruction { name: "*LAX", bytes: 3, cycles: 4, addr_mode: AddressingMode::AbsoluteY },
+        0xa3 => Instruction { name: "*LAX", bytes: 2, cycles: 6, addr_mode: AddressingMode::IndirectX },
+        0xb3 => Instruction { name: "*LAX", bytes: 2, cycles: 5, addr_mode: AddressingMode::IndirectY },
+
+        0x87 => Instruction { name: "*SAX", bytes: 2, cycles: 3, addr_mode: AddressingMode::ZeroPage },
+        0x97 => Instruction { name: "*SAX", bytes: 2, cycles: 4, addr_mode: AddressingMode::ZeroPageY },
+        0x8f => Instruction { name: "*SAX", bytes: 3, cycles: 4, addr_mode: AddressingMode::Absolute },
+        0x83 => Instruction { name: "*SAX", bytes: 2, cycles: 6, addr_mode: AddressingMode::IndirectX },
     }
+}
+
+const fn with<M: Memory>(mut table: [Handler<M>; 256], opcodes: &[u8], handler: Handler<M>) -> [Handler<M>; 256] {
+    let mut i = 0;
+    while i < opcodes.len() {
+        table[opcodes[i] as usize] = handler;
+        i += 1;
+    }
+    table
+}
+
+impl<M: Memory> CPU<M> {
+    // Handlers indexed by opcode, BRK (0x00) is handled by the executor itself.
+    // This lives beside INSTRUCTIONS rather than in it as the function pointers depend on the memory type.
+    pub(crate) const HANDLERS: [Handler<M>; 256] = {
+        let mut t: [Handler<M>; 256] = [|_, _| {}; 256];
+        t = with(t, &[0x69, 0x65, 0x75, 0x6D, 0x7D, 0x79, 0x61, 0x71], |cpu, mode| cpu.adc(mode));
+        t = with(t, &[0x29, 0x25, 0x35, 0x2D, 0x3D, 0x39, 0x21, 0x31], |cpu, mode| cpu.and(mode));
+        t = with(t, &[0x0A], |cpu, _| cpu.asl_accumulator());
+        t = with(t, &[0x06, 0x16, 0x0E, 0x1E], |cpu, mode| { cpu.asl(mode); });
+        t = with(t, &[0x90], |cpu, _| cpu.bcc());
+        t = with(t, &[0xB0], |cpu, _| cpu.bcs());
+        t = with(t, &[0xF0], |cpu, _| cpu.beq());
+        t = with(t, &[0x24, 0x2C], |cpu, mode| cpu.bit(mode));
+        t = with(t, &[0x30], |cpu, _| cpu.bmi());
+        t = with(t, &[0xD0], |cpu, _| cpu.bne());
+        t = with(t, &[0x10], |cpu, _| cpu.bpl());
+        t = with(t, &[0x50], |cpu, _| cpu.bvc());
+        t = with(t, &[0x70], |cpu, _| cpu.bvs());
+        t = with(t, &[0x18], |cpu, _| cpu.clc());
+        t = with(t, &[0xD8], |cpu, _| cpu.cld());
+        t = with(t, &[0x58], |cpu, _| cpu.cli());
+        t = with(t, &[0xB8], |cpu, _| cpu.clv());
+        t = with(t, &[0xC9, 0xC5, 0xD5, 0xCD, 0xDD, 0xD9, 0xC1, 0xD1], |cpu, mode| cpu.compare(mode, cpu.regs.a));
+        t = with(t, &[0xE0, 0xE4, 0xEC], |cpu, mode| cpu.compare(mode, cpu.regs.x));
+        t = with(t, &[0xC0, 0xC4, 0xCC], |cpu, mode| cpu.compare(mode, cpu.regs.y));
+        t = with(t, &[0xC6, 0xD6, 0xCE, 0xDE], |cpu, mode| cpu.dec(mode));
+        t = with(t, &[0xCA], |cpu, _| cpu.dex());
+        t = with(t, &[0x88], |cpu, _| cpu.dey());
+        t = with(t, &[0x49, 0x45, 0x55, 0x4D, 0x5D, 0x59, 0x41, 0x51], |cpu, mode| cpu.eor(mode));
+        t = with(t, &[0xE6, 0xF6, 0xEE, 0xFE], |cpu, mode| { cpu.inc(mode); });
+        t = with(t, &[0xE8], |cpu, _| cpu.inx());
+        t = with(t, &[0xC8], |cpu, _| cpu.iny());
+        t = with(t, &[0x4C], |cpu, _| cpu.jmp());
+        t = with(t, &[0x6C], |cpu, _| cpu.jmp_indirect());
+        t = with(t, &[0x20], |cpu, _| cpu.jsr());
+        t = with(t, &[0xA9, 0xA5, 0xB5, 0xAD, 0xBD, 0xB9, 0xA1, 0xB1], |cpu, mode| cpu.lda(mode));
+        t = with(t, &[0xA2, 0xA6, 0xB6, 0xAE, 0xBE], |cpu, mode| cpu.ldx(mode));
+        t = with(t, &[0xA0, 0xA4, 0xB4, 0xAC, 0xBC], |cpu, mode| cpu.ldy(mode));
+        t = with(t, &[0x4A], |cpu, _| cpu.lsr_accumulator());
+        t = with(t, &[0x46, 0x56, 0x4E, 0x5E], |cpu, mode| { cpu.lsr(mode); });
+        t = with(t, &[0xEA], |_, _| {});
+        t = with(t, &[0x09, 0x05, 0x15, 0x0D, 0x1D, 0x19, 0x01, 0x11], |cpu, mode| cpu.ora(mode));
+        t = with(t, &[0x48], |cpu, _| cpu.pha());
+        t = with(t, &[0x08], |cpu, _| cpu.php());
+        t = with(t, &[0x68], |cpu, _| cpu.pla());
+        t = with(t, &[0x28], |cpu, _| cpu.plp());
+        t = with(t, &[0x2A], |cpu, _| cpu.rol_accumulator());
+        t = with(t, &[0x26, 0x36, 0x2E, 0x3E], |cpu, mode| { cpu.rol(mode); });
+        t = with(t, &[0x6A], |cpu, _| cpu.ror_accumulator());
+        t = with(t, &[0x66, 0x76, 0x6E, 0x7E], |cpu, mode| { cpu.ror(mode); });
+        t = with(t, &[0x40], |cpu, _| cpu.rti());
+        t = with(t, &[0x60], |cpu, _| cpu.rts());
+        t = with(t, &[0xE9, 0xE5, 0xF5, 0xED, 0xFD, 0xF9, 0xE1, 0xF1], |cpu, mode| cpu.sbc(mode));
+        t = with(t, &[0x38], |cpu, _| set_bit(&mut cpu.regs.p, CPUStatusFlags::CarryFlag as u8, true));
+        t = with(t, &[0xF8], |cpu, _| set_bit(&mut cpu.regs.p, CPUStatusFlags::DecimalMode as u8, true));
+        t = with(t, &[0x78], |cpu, _| set_bit(&mut cpu.regs.p, CPUStatusFlags::InterruptDisable as u8, true));
+        t = with(t, &[0x85, 0x95, 0x8D, 0x9D, 0x99, 0x81, 0x91], |cpu, mode| cpu.sta(mode));
+        t = with(t, &[0x86, 0x96, 0x8E], |cpu, mode| cpu.stx(mode));
+        t = with(t, &[0x84, 0x94, 0x8C], |cpu, mode| cpu.sty(mode));
+        t = with(t, &[0xAA], |cpu, _| cpu.tax());
+        t = with(t, &[0xA8], |cpu, _| cpu.tay());
+        t = with(t, &[0xBA], |cpu, _| cpu.tsx());
+        t = with(t, &[0x8A], |cpu, _| cpu.txa());
+        t = with(t, &[0x9A], |cpu, _| cpu.txs());
+        t = with(t, &[0x98], |cpu, _| cpu.tya());
+
+        // Unofficial Opcodes
+        t = with(t, &[0xC7, 0xD7, 0xCF, 0xDF, 0xDB, 0xD3, 0xC3], |cpu, mode| cpu.dcp(mode));
+        t = with(t, &[0x27, 0x37, 0x2F, 0x3F, 0x3B, 0x33, 0x23], |cpu, mode| cpu.rla(mode));
+        t = with(t, &[0x07, 0x17, 0x0F, 0x1F, 0x1B, 0x03, 0x13], |cpu, mode| cpu.slo(mode));
+        t = with(t, &[0x47, 0x57, 0x4F, 0x5f, 0x5b, 0x43, 0x53], |cpu, mode| cpu.sre(mode));
+
+        /* SKB, 2 byte NOP (immidiate) */
+        t = with(t, &[0x80, 0x82, 0x89, 0xC2, 0xE2], |_, _| {});
+        t = with(t, &[0xCB], |cpu, mode| cpu.axs(mode));
+        t = with(t, &[0x6B], |cpu, mode| cpu.arr(mode));
+        t = with(t, &[0xEB], |cpu, mode| cpu.sbc_unofficial(mode));
+        t = with(t, &[0x0B, 0x2B], |cpu, mode| cpu.anc(mode));
+        t = with(t, &[0x4B], |cpu, mode| cpu.alr(mode));
+
+        /* NOP read */
+        t = with(t, &[0x04, 0x44, 0x64, 0x14, 0x34, 0x54, 0x74, 0xD4, 0xF4, 0x0C, 0x1C, 0x3C, 0x5C, 0x7C, 0xDC, 0xFC], |cpu, mode| cpu.nop_read(mode));
+
+        t = with(t, &[0x67, 0x77, 0x6F, 0x7F, 0x7B, 0x63, 0x73], |cpu, mode| cpu.rra(mode));
+        t = with(t, &[0xE7, 0xF7, 0xEF, 0xFF, 0xFB, 0xE3, 0xF3], |cpu, mode| cpu.isb(mode));
+        t = with(t, &[0x02, 0x12, 0x22, 0x32, 0x42, 0x52, 0x62, 0x72, 0x92, 0xB2, 0xD2, 0xF2], |_, _| {});
+        t = with(t, &[0x1A, 0x3A, 0x5A, 0x7A, 0xDA, 0xFA], |_, _| {});
+        t = with(t, &[0xA7, 0xB7, 0xAF, 0xBF, 0xA3, 0xB3], |cpu, mode| cpu.lax(mode));
+        t = with(t, &[0x87, 0x97, 0x8F, 0x83], |cpu, mode| cpu.sax(mode));
+        t = with(t, &[0xAB], |cpu, mode| cpu.lxa(mode));
+        t = with(t, &[0x8B], |cpu, mode| cpu.xaa(mode));
+        t = with(t, &[0xBB], |cpu, mode| cpu.las(mode));
+        t = with(t, &[0x9B], |cpu, _| cpu.tas());
+        t = with(t, &[0x93], |cpu, _| cpu.ahx_indir_y());
+        t = with(t, &[0x9F], |cpu, _| cpu.ahx_abs_y());
+        t = with(t, &[0x9E], |cpu, _| cpu.shx());
+        t = with(t, &[0x9C], |cpu, _| cpu.shy());
+
+        t
+    };
 
     fn update_result_flags(&mut self, result: u8) {
         set_bit(&mut self.regs.p, CPUStatusFlags::ZeroResult as u8, result == 0);
@@ -374,11 +497,11 @@ impl CPU<'_> {
 
     fn stack_pop(&mut self) -> u8 {
         self.regs.sp = self.regs.sp.wrapping_add(1);
-        self.read((STACK as u16) + self.regs.sp as u16)
+        self.read(STACK + self.regs.sp as u16)
     }
 
     pub fn stack_push(&mut self, data: u8) {
-        self.write((STACK as u16) + self.regs.sp as u16, data);
+        self.write(STACK + self.regs.sp as u16, data);
         self.regs.sp = self.regs.sp.wrapping_sub(1)
     }
 
@@ -880,6 +1003,15 @@ impl CPU<'_> {
         self.lsr_accumulator();
     }
 
+    pub(crate) fn nop_read(&mut self, mode: &AddressingMode) {
+        let (addr, page_crossed) = self.get_op_addr(mode);
+        let _value = self.read(addr);
+
+        if page_crossed {
+            self.bus.tick(1);
+        }
+    }
+
     pub(crate) fn rra(&mut self, mode: &AddressingMode) {
         let value = self.ror(mode);
 
@@ -1,6 +1,7 @@
-use crate::lib::{set_bit, get_bit};
+use crate::{set_bit, get_bit};
+use crate::devices::PortDevice;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
 pub enum Inputs {
     Right,
     Left,
@@ -12,6 +13,28 @@ pub enum Inputs {
     A,
 }
 
+impl Inputs {
+    // In bit order, from A in bit 0 through to Right in bit 7
+    pub const ALL: [Inputs; 8] = [
+        Inputs::A, Inputs::B, Inputs::Select, Inputs::Start,
+        Inputs::Up, Inputs::Down, Inputs::Left, Inputs::Right,
+    ];
+
+    pub fn bit(self) -> u8 {
+        match self {
+            Inputs::Right => 7,
+            Inputs::Left => 6,
+            Inputs::Down => 5,
+            Inputs::Up => 4,
+            Inputs::Start => 3,
+            Inputs::Select => 2,
+            Inputs::B => 1,
+            Inputs::A => 0,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Joypad {
     strobe: bool,
     button_index: u8,
@@ -27,7 +50,23 @@ impl Joypad {
         }
     }
 
-    pub fn read(&mut self) -> u8 {
+    // Sets every button at once, bit 0 is A through to bit 7 for Right
+    pub fn set_buttons(&mut self, mask: u8) {
+        self.button_status = mask;
+    }
+
+    pub fn buttons(&self) -> u8 {
+        self.button_status
+    }
+
+    pub fn set_button_pressed_status(&mut self, button: Inputs, pressed: bool) {
+        set_bit(&mut self.button_status, button.bit(), pressed);
+    }
+}
+
+// The standard controller shifts out one button per read on D0, then reads as 1 once all eight are done
+impl PortDevice for Joypad {
+    fn read(&mut self) -> u8 {
         if self.button_index > 7 {
             return 1;
         }
@@ -38,26 +77,12 @@ impl Joypad {
         response
     }
 
-    pub fn write(&mut self, data: u8) {
+    fn strobe(&mut self, data: u8) {
         self.strobe = get_bit(data, 0);
         if self.strobe {
             self.button_index = 0
         }
     }
-
-    pub fn set_button_pressed_status(&mut self, button: Inputs, pressed: bool) {
-        let index = match button {
-            Inputs::Right => 7,
-            Inputs::Left => 6,
-            Inputs::Down => 5,
-            Inputs::Up => 4,
-            Inputs::Start => 3,
-            Inputs::Select => 2,
-            Inputs::B => 1,
-            Inputs::A => 0,
-        };
-        set_bit(&mut self.button_status, index, pressed);
-    }
 }
 
 impl Default for Joypad {
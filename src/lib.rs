@@ -1,3 +1,42 @@
+pub mod cpu;
+pub mod registers;
+pub mod instructions;
+pub mod bus;
+pub mod archive;
+pub mod cart;
+pub mod game_db;
+pub mod debug;
+pub mod ppu;
+pub mod joypad;
+pub mod devices;
+pub mod nes;
+pub mod env;
+pub mod vec_nes;
+pub mod rendering;
+pub mod state;
+pub mod slots;
+pub mod rewind;
+pub mod movie;
+pub mod patch;
+pub mod turbo;
+pub mod input_macro;
+pub mod test_rom;
+#[cfg(test)]
+mod single_step;
+#[cfg(test)]
+mod test_util;
+
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate rmp_serde as rmps;
+
+pub use bus::Memory;
+pub use cpu::CPU;
+
+pub const WINDOW_WIDTH: usize = 256;
+pub const WINDOW_HEIGHT: usize = 240;
+
 pub fn get_bit(input: u8, index: u8) -> bool {
     input & (1 << index) != 0
 }
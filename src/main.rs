@@ -1,52 +1,263 @@
+mod bindings;
+mod cli;
+mod gamepads;
+
 use std::collections::HashMap;
-use std::env;
+use std::path::Path;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use bus::{Bus, GameloopAction};
-use cart::Cart;
-use cpu::CPU;
-use joypad::Inputs;
-use rendering::{Frame, render};
+use nes_emulator_rs::cart::{mapper_name, Cart, LoadOptions, Region, RomHeader};
+use nes_emulator_rs::devices::Device;
+use nes_emulator_rs::game_db::GameDb;
+use nes_emulator_rs::input_macro::{InputMacro, MacroPlayer};
+use nes_emulator_rs::movie::{Movie, Playback};
+use nes_emulator_rs::nes::Nes;
+use nes_emulator_rs::rendering::load_palette;
+use nes_emulator_rs::rewind::Rewind;
+use nes_emulator_rs::slots::SaveSlots;
+use nes_emulator_rs::turbo::Turbo;
+use nes_emulator_rs::{WINDOW_WIDTH, WINDOW_HEIGHT};
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
 use sdl2::pixels::PixelFormatEnum;
 
-pub mod cpu;
-pub mod registers;
-pub mod lib;
-pub mod instructions;
-pub mod bus;
-pub mod cart;
-pub mod debug;
-pub mod ppu;
-pub mod joypad;
-pub mod rendering;
-pub mod state;
-
-extern crate serde;
-#[macro_use]
-extern crate serde_derive;
-extern crate serde_big_array;
-extern crate rmp_serde as rmps;
-
-const WINDOW_WIDTH: usize = 256;
-const WINDOW_HEIGHT: usize = 240;
+use bindings::{Action, BindPrompt, Bindings, MacroBinding};
+use cli::Options;
+use gamepads::Gamepads;
+
+// Snapshot every other frame, keeping up to 32 MiB of them
+const REWIND_INTERVAL: usize = 2;
+const REWIND_BUDGET: usize = 32 * 1024 * 1024;
+
+// Plays a movie back a frame at a time, reporting how it went
+struct MoviePlayer {
+    movie: Movie,
+    frame: usize,
+    diverged: bool,
+}
+
+impl MoviePlayer {
+    // Returns false once the movie has finished
+    fn play_frame(&mut self, nes: &mut Nes) -> bool {
+        match self.movie.play_frame(self.frame, nes) {
+            Playback::Played => {}
+            // Only the first is worth reporting, everything after it follows on
+            Playback::Diverged(divergence) => {
+                if !self.diverged {
+                    eprintln!("{}", divergence);
+                    self.diverged = true;
+                }
+            }
+            Playback::Finished => {
+                println!("Movie finished after {} frames", self.frame);
+                return false;
+            }
+        }
+        self.frame += 1;
+        true
+    }
+}
 
 fn main() {
-    // Get rom path from cmd line arg
-    let args: Vec<String> = env::args().collect();
-    let cart_path = args.get(1).expect("No ROM path provided");
-    let cart = Cart::new(cart_path);
+    let options = cli::parse();
+    if options.info {
+        process::exit(print_info(&options.rom, &options.load));
+    }
+    let rom = options.rom.to_string_lossy().into_owned();
+    let cart = Cart::load_with(&rom, &options.load).unwrap_or_else(|err| exit_with_error(&format!("Unable to load ROM {}: {}", rom, err)));
+
+    let mut nes = Nes::new(cart);
+    for (port, device) in options.devices.into_iter().enumerate() {
+        if let Some(device) = device {
+            nes.connect(port, device);
+        }
+    }
+    if let Some(region) = options.region {
+        nes.set_region(region);
+        nes.power_cycle();
+    }
+    if let Some(path) = &options.palette {
+        let palette = load_palette(path).unwrap_or_else(|err| exit_with_error(&format!("Invalid palette {}: {}", path.display(), err)));
+        nes.set_palette(palette);
+    }
+
+    let slots = SaveSlots::new(SaveSlots::default_root(), nes.rom_hash());
+    if let Some(slot) = options.load_slot {
+        if let Err(err) = slots.load(slot, &mut nes) {
+            exit_with_error(&format!("Unable to load save state {}: {}", slot, err));
+        }
+    }
+
+    // Recording after loading a state starts the movie from that state
+    let recording = options.record.as_ref().map(|_| match options.load_slot {
+        Some(_) => Movie::record_from_state(&nes),
+        None => Movie::record_from_power_on(&mut nes),
+    });
+    let playing = options.play.as_ref().map(|path| {
+        let movie = Movie::load(path, nes.rom_hash()).unwrap_or_else(|err| exit_with_error(&format!("Unable to load movie {}: {}", path, err)));
+        if let Err(err) = movie.start(&mut nes) {
+            exit_with_error(&format!("Unable to play movie {}: {}", path, err));
+        }
+        MoviePlayer { movie, frame: 0, diverged: false }
+    });
+
+    let recording = match options.headless {
+        Some(frames) => run_headless(&mut nes, &options, frames, recording, playing),
+        None => run_windowed(&mut nes, &options, &slots, recording, playing),
+    };
+
+    if let (Some(movie), Some(path)) = (recording, &options.record) {
+        if let Err(err) = movie.save(path) {
+            eprintln!("Failed to save movie {}: {}", path, err);
+        }
+    }
+    if options.screenshot_frame.is_none() {
+        take_screenshot(&nes, &options);
+    }
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+/*
+    Prints the ROM header as `Field: value` lines, after patching and game database corrections, then the
+    hashes if the ROM is all there. Structural problems go to stderr and give an exit code of 1, so ROM
+    sets can be checked with a script.
+*/
+fn print_info(path: &Path, options: &LoadOptions) -> i32 {
+    let fail = |err: &dyn std::fmt::Display| {
+        eprintln!("{}: {}", path.display(), err);
+        1
+    };
+    let data = match Cart::read(&path.to_string_lossy(), options) {
+        Ok(data) => data,
+        Err(err) => return fail(&err),
+    };
+    let header = match RomHeader::parse(&data) {
+        Ok(header) => header,
+        Err(err) => return fail(&err),
+    };
+    let file_size = data.len();
+    let mut cart = Cart::from_bytes(&path.to_string_lossy(), data);
+
+    // Corrections need the hashes, so a truncated ROM is shown as it is
+    let mut database = None;
+    if let (Ok(cart), true) = (&mut cart, options.game_db) {
+        database = GameDb::builtin().find(cart.crc32(), &cart.sha1()).map(|entry| (entry, cart.rom_header.correct(entry)));
+    }
+    let header = cart.as_ref().map_or(&header, |cart| &cart.rom_header);
+
+    let yes_no = |flag: bool| if flag { "yes" } else { "no" };
+    println!("Format:      {}", if header.is_nes2() { "NES 2.0" } else { "iNES" });
+    println!("Mapper:      {} ({})", header.mapper(), mapper_name(header.mapper()).unwrap_or("unknown"));
+    println!("Submapper:   {}", header.submapper());
+    println!("Supported:   {}", yes_no(header.mapper_supported()));
+    println!("PRG ROM:     {} KiB", header.prg_rom_size() / 1024);
+    println!("CHR ROM:     {} KiB", header.chr_rom_size() / 1024);
+    println!("Mirroring:   {:?}", header.screen_mirroring);
+    println!("Battery:     {}", yes_no(header.has_battery()));
+    println!("Trainer:     {}", yes_no(header.has_trainer()));
+    println!("Region:      {}", header.region);
+    println!("Expansion:   {:#04x}", header.expansion_device);
+
+    match &cart {
+        Ok(cart) => {
+            println!("CRC32:       {:08X}", cart.crc32());
+            println!("SHA-1:       {}", cart.sha1().iter().map(|byte| format!("{:02X}", byte)).collect::<String>());
+            match database {
+                Some((entry, corrected)) if corrected.is_empty() => println!("Database:    {} (header is correct)", entry.name),
+                Some((entry, corrected)) => println!("Database:    {} (corrected {})", entry.name, corrected.join(", ")),
+                None => println!("Database:    none"),
+            }
+            match options.patch_path(path) {
+                Some(patch) => println!("Patch:       {}", patch.display()),
+                None => println!("Patch:       none"),
+            }
+            match header.check_size(file_size) {
+                Ok(()) => 0,
+                Err(err) => fail(&err),
+            }
+        }
+        Err(err) => fail(&err),
+    }
+}
+
+// Saves the screenshot if one was asked for
+fn take_screenshot(nes: &Nes, options: &Options) {
+    if let Some(path) = &options.screenshot {
+        match nes.framebuffer().save_png(path) {
+            Ok(()) => println!("Saved screenshot to {}", path.display()),
+            Err(err) => eprintln!("Failed to save screenshot {}: {}", path.display(), err),
+        }
+    }
+}
+
+// Runs a number of frames as fast as possible with no window and no input other than a movie
+fn run_headless(nes: &mut Nes, options: &Options, frames: usize, mut recording: Option<Movie>, mut playing: Option<MoviePlayer>) -> Option<Movie> {
+    for frame in 1..=frames {
+        if let Some(movie) = &mut recording {
+            movie.record_frame(nes);
+        } else if let Some(player) = &mut playing {
+            if !player.play_frame(nes) {
+                playing = None;
+                nes.run_frame();
+            }
+        } else {
+            nes.run_frame();
+        }
+        if options.screenshot_frame == Some(frame) {
+            take_screenshot(nes, options);
+        }
+    }
+    recording
+}
+
+fn run_windowed(nes: &mut Nes, options: &Options, slots: &SaveSlots, mut recording: Option<Movie>, mut playing: Option<MoviePlayer>) -> Option<Movie> {
+    let bindings_path = options.bindings.clone().unwrap_or_else(Bindings::default_path);
+    let mut bindings = Bindings::load(&bindings_path).unwrap_or_else(|err| exit_with_error(&format!("Invalid bindings {}", err)));
+    for binding in &options.binds {
+        if let Err(err) = bindings.set(binding) {
+            exit_with_error(&format!("Invalid --bind {}: {}", binding, err));
+        }
+    }
+    let mut keymap = bindings.keymap().unwrap();
+    let mut bind_prompt: Option<BindPrompt> = None;
+    let mut turbo = Turbo::with_rate(options.turbo_rate.unwrap_or(bindings.turbo_rate));
+    let mut macro_players: Vec<MacroPlayer> = Vec::new();
+    let mut recording_macro: Option<InputMacro> = None;
+    // A macro that has been recorded and is waiting for a key
+    let mut macro_to_bind: Option<InputMacro> = None;
     
     // Init SLD2
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
-    let window = video_subsystem
-        .window("NES", (WINDOW_WIDTH * 2) as u32, (WINDOW_HEIGHT * 2) as u32)
-        .position_centered().build().unwrap();
+    let mut window = video_subsystem.window("NES", WINDOW_WIDTH as u32 * options.scale, WINDOW_HEIGHT as u32 * options.scale);
+    window.position_centered().resizable();
+    if options.fullscreen {
+        window.fullscreen_desktop();
+    }
+    let window = window.build().unwrap();
+
+    // Vsync keeps NTSC at the right speed on a 60Hz display, PAL is paced by sleeping instead
+    let pal = nes.region() == Region::Pal;
+    let mut canvas = if pal { window.into_canvas().build() } else { window.into_canvas().present_vsync().build() }.unwrap();
+    let frame_time = Duration::from_secs_f64(1.0 / nes.region().frame_rate());
+    let mut next_frame = Instant::now();
 
-    let mut canvas = window.into_canvas().present_vsync().build().unwrap();
     let mut event_pump = sdl_context.event_pump().unwrap();
-    canvas.set_scale(2.0, 2.0).unwrap();
+    let mut gamepads = Gamepads::new(
+        sdl_context.game_controller().unwrap(),
+        sdl_context.joystick().unwrap(),
+        bindings.button_map().unwrap(),
+        options.pads.clone(),
+    );
+    // Scales the picture to the window and mouse positions back to NES pixels
+    canvas.set_logical_size(WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32).unwrap();
 
     let creator = canvas.texture_creator();
     let mut texture = creator.create_texture_target(
@@ -55,52 +266,203 @@ fn main() {
         (WINDOW_HEIGHT) as u32
     ).unwrap();
 
-    let key_map = HashMap::from([
-        (Keycode::A, Inputs::A),
-        (Keycode::S, Inputs::B),
-        (Keycode::Space, Inputs::Select),
-        (Keycode::Return, Inputs::Start),
-        (Keycode::Up, Inputs::Up),
-        (Keycode::Down, Inputs::Down),
-        (Keycode::Left, Inputs::Left),
-        (Keycode::Right, Inputs::Right),
-    ]);
-
-    let mut frame = Frame::new();
-    
-    let bus = Bus::new(cart, |ppu, joypad| {
-        render(ppu, &mut frame);
-        texture.update(None, &frame.data, WINDOW_WIDTH * 2 * 3).unwrap();
+    let mut slot = options.load_slot.unwrap_or(1);
+    let mut rewind = Rewind::new(REWIND_INTERVAL, REWIND_BUDGET);
+    let mut rewinding = false;
+    let mut rewind_exhausted = false;
+    let mut frame = 0;
+
+    'gameloop: loop {
+        // Turbo and macros would desync a movie being played back
+        if bind_prompt.is_none() && playing.is_none() {
+            turbo.apply(nes);
+            macro_players.retain_mut(|player| player.apply(nes));
+            if let Some(input_macro) = &mut recording_macro {
+                input_macro.push_frame(nes.buttons(0));
+            }
+        }
+
+        // The game is paused while keys are being bound
+        if bind_prompt.is_none() {
+            if let Some(movie) = &mut recording {
+                movie.record_frame(nes);
+            } else if let Some(player) = &mut playing {
+                if !player.play_frame(nes) {
+                    playing = None;
+                }
+            } else {
+                // Rewinding is only possible outside of movies, it would desync them from their input
+                if rewinding {
+                    let exhausted = !rewind.rewind(nes);
+                    if exhausted && !rewind_exhausted {
+                        println!("Reached the oldest rewind snapshot");
+                    }
+                    rewind_exhausted = exhausted;
+                } else {
+                    nes.run_frame();
+                    rewind.push(nes);
+                    rewind_exhausted = false;
+                }
+            }
+
+            frame += 1;
+            if options.screenshot_frame == Some(frame) {
+                take_screenshot(nes, options);
+            }
+        }
+
+        texture.update(None, &nes.framebuffer().data, WINDOW_WIDTH * 2 * 3).unwrap();
 
         canvas.copy(&texture, None, None).unwrap();
 
         canvas.present();
+        if pal {
+            next_frame += frame_time;
+            match next_frame.checked_duration_since(Instant::now()) {
+                Some(wait) => thread::sleep(wait),
+                // Running behind, don't try to catch up
+                None => next_frame = Instant::now(),
+            }
+        }
         for event in event_pump.poll_iter() {
+            if gamepads.handle_event(nes, &mut turbo, &event) {
+                continue;
+            }
             match event {
-                Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), ..} => std::process::exit(0),
+                Event::Quit { .. } => break 'gameloop,
 
-                Event::KeyDown { keycode: Some(Keycode::Z), .. } => return GameloopAction::SaveState, 
+                Event::KeyDown { keycode: Some(keycode), repeat: false, .. } if bind_prompt.is_some() => {
+                    if bind_prompt.as_mut().unwrap().key_pressed(&mut bindings, keycode) {
+                        continue;
+                    }
+                    bind_prompt = None;
+                    update_bindings(&bindings, &bindings_path, &mut keymap);
+                }
 
-                Event::KeyDown { keycode: Some(Keycode::X), .. } => return GameloopAction::LoadState, 
- 
-                Event::KeyDown { keycode, .. } => {
-                    if let Some(key) = key_map.get(&keycode.unwrap_or(Keycode::Ampersand)) {
-                        joypad.set_button_pressed_status(*key, true);
+                Event::KeyDown { keycode: Some(keycode), repeat: false, .. } if macro_to_bind.is_some() => {
+                    let input_macro = macro_to_bind.take().unwrap();
+                    if keycode == Keycode::Escape {
+                        println!("Macro discarded");
+                        continue;
                     }
+                    println!("Macro bound to {}", keycode.name());
+                    bindings.macros.push(MacroBinding { key: keycode.name(), player: 1, steps: input_macro.steps });
+                    update_bindings(&bindings, &bindings_path, &mut keymap);
                 }
-                
-                Event::KeyUp { keycode, .. } => {
-                    if let Some(key) = key_map.get(&keycode.unwrap_or(Keycode::Ampersand)) {
-                        joypad.set_button_pressed_status(*key, false);
+
+                Event::KeyDown { keycode: Some(keycode), repeat: false, .. } => {
+                    for &action in keymap.get(&keycode).into_iter().flatten() {
+                        match action {
+                            Action::Quit => break 'gameloop,
+                            Action::SaveState => {
+                                if let Err(err) = slots.save(slot, nes) {
+                                    eprintln!("Failed to save state {}: {}", slot, err);
+                                }
+                            }
+                            Action::LoadState => {
+                                if let Err(err) = slots.load(slot, nes) {
+                                    eprintln!("Failed to load state {}: {}", slot, err);
+                                }
+                            }
+                            Action::Slot(selected) => {
+                                slot = selected;
+                                println!("Selected save slot {}", slot);
+                            }
+                            Action::Rewind => rewinding = true,
+                            Action::Bind => bind_prompt = BindPrompt::new(&mut bindings),
+                            Action::RecordMacro => match recording_macro.take() {
+                                None => {
+                                    println!("Recording a macro from player 1, press {} again to stop", bindings.hotkeys.record_macro);
+                                    recording_macro = Some(InputMacro::default());
+                                }
+                                Some(mut input_macro) => {
+                                    input_macro.trim();
+                                    if input_macro.is_empty() {
+                                        println!("Nothing was pressed, macro discarded");
+                                    } else {
+                                        println!("Recorded {} frames, press a key to play the macro with or Escape to discard it", input_macro.len());
+                                        macro_to_bind = Some(input_macro);
+                                    }
+                                }
+                            },
+                            Action::Macro(index) => {
+                                let binding = &bindings.macros[index];
+                                macro_players.push(MacroPlayer::new(binding.input_macro(), binding.player - 1));
+                            }
+                            Action::Turbo(player, input) => turbo.set_held(nes, player, input, true),
+                            Action::Button(..) | Action::PowerPad(_) => press(nes, action, true),
+                        }
                     }
                 }
+
+                Event::KeyUp { keycode: Some(keycode), .. } => {
+                    for &action in keymap.get(&keycode).into_iter().flatten() {
+                        match action {
+                            Action::Rewind => rewinding = false,
+                            Action::Turbo(player, input) => turbo.set_held(nes, player, input, false),
+                            Action::Button(..) | Action::PowerPad(_) => press(nes, action, false),
+                            _ => {}
+                        }
+                    }
+                }
+
+                // The Zapper and Arkanoid paddle follow the mouse
+                Event::MouseMotion { x, y, .. } => {
+                    let (x, y) = (x.max(0) as usize, y.max(0) as usize);
+                    for port in 0..2 {
+                        match nes.device_mut(port) {
+                            Device::Zapper(zapper) => zapper.aim(x, y),
+                            Device::Arkanoid(paddle) => paddle.move_to(x),
+                            _ => {}
+                        }
+                    }
+                }
+
+                Event::MouseButtonDown { mouse_btn: MouseButton::Left, .. } => press_mouse(nes, true),
+
+                Event::MouseButtonUp { mouse_btn: MouseButton::Left, .. } => press_mouse(nes, false),
                 _ => {}
             }
         }
-        GameloopAction::NoAction
-    });
+    }
 
-    let mut cpu = CPU::new(bus);
-    cpu.reset();
-    cpu.run();
-}
\ No newline at end of file
+    recording
+}
+
+// Rebuilds the keymap from changed bindings and saves them
+fn update_bindings(bindings: &Bindings, path: &Path, keymap: &mut HashMap<Keycode, Vec<Action>>) {
+    match bindings.keymap() {
+        Ok(new_keymap) => {
+            *keymap = new_keymap;
+            match bindings.save(path) {
+                Ok(()) => println!("Saved bindings to {}", path.display()),
+                Err(err) => eprintln!("Failed to save bindings to {}: {}", path.display(), err),
+            }
+        }
+        Err(err) => eprintln!("Bindings not changed: {}", err),
+    }
+}
+
+fn press(nes: &mut Nes, action: Action, pressed: bool) {
+    match action {
+        Action::Button(player, input) => nes.set_button_pressed_status(player, input, pressed),
+        Action::PowerPad(button) => {
+            for port in 0..2 {
+                if let Device::PowerPad(pad) = nes.device_mut(port) {
+                    pad.set_button_pressed_status(button, pressed);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn press_mouse(nes: &mut Nes, pressed: bool) {
+    for port in 0..2 {
+        match nes.device_mut(port) {
+            Device::Zapper(zapper) => zapper.trigger = pressed,
+            Device::Arkanoid(paddle) => paddle.button = pressed,
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,517 @@
+/*
+    Input movies: the controller input for every frame, played back from power-on or from a save state.
+
+    Movies are saved in their own format, which checks the ROM and can start from a save state, or
+    imported and exported as FCEUX FM2 text so that existing TAS movies can be replayed.
+
+    Recorded movies also hold a hash of the work RAM and the frame buffer after every frame. Playing one
+    back checks them, so a movie doubles as a test that the emulator still behaves exactly as it did.
+
+    Only standard controllers are recorded, up to four with a Four Score. Ports with other devices record no input.
+*/
+use std::path::Path;
+use std::{fmt, fs, io};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+
+use serde::Deserialize;
+
+use crate::cart::Region;
+use crate::devices::DeviceType;
+use crate::nes::{Nes, PowerOnState};
+use crate::state::StateError;
+
+const MAGIC: [u8; 4] = *b"NESM";
+const FORMAT_VERSION: u16 = 5;
+
+// RAM is hashed in pages so a divergence can say roughly where it is
+const RAM_PAGE_SIZE: usize = 256;
+const RAM_PAGES: usize = 2048 / RAM_PAGE_SIZE;
+
+// Commands run before a frame's input is applied, the same bits FM2 uses
+pub const COMMAND_RESET: u8 = 1;
+pub const COMMAND_POWER: u8 = 2;
+
+// FM2 lists buttons from bit 7 down to bit 0
+const FM2_BUTTONS: &[u8; 8] = b"RLDUTSBA";
+
+#[derive(Debug)]
+pub enum MovieError {
+    Io(io::Error),
+    NotAMovie,
+    UnsupportedVersion(u16),
+    WrongRom { expected: u32, found: u32 },
+    State(StateError),
+    // The header is fine but what follows can't be decoded, e.g. a truncated file
+    Corrupt(String),
+    Fm2(String),
+    Diverged(Divergence),
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MovieError::Io(err) => write!(f, "{}", err),
+            MovieError::NotAMovie => write!(f, "Data is not a movie"),
+            MovieError::UnsupportedVersion(version) => write!(f, "Movie version {} is not supported, expected {}", version, FORMAT_VERSION),
+            MovieError::WrongRom { expected, found } => write!(f, "Movie is for ROM {:08X}, loaded ROM is {:08X}", found, expected),
+            MovieError::State(err) => write!(f, "Movie start state: {}", err),
+            MovieError::Corrupt(err) => write!(f, "Movie is corrupt: {}", err),
+            MovieError::Fm2(err) => write!(f, "FM2: {}", err),
+            MovieError::Diverged(divergence) => write!(f, "{}", divergence),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+impl From<io::Error> for MovieError {
+    fn from(err: io::Error) -> Self {
+        MovieError::Io(err)
+    }
+}
+
+impl From<StateError> for MovieError {
+    fn from(err: StateError) -> Self {
+        MovieError::State(err)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct MovieFrame {
+    pub commands: u8,
+    // Button mask for each player, bit 0 is A through to bit 7 for Right. Players 3 and 4 need a Four Score
+    pub players: [u8; 4],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct FrameHash {
+    pub ram: [u32; RAM_PAGES],
+    pub framebuffer: u32,
+}
+
+impl FrameHash {
+    pub fn of(nes: &Nes) -> Self {
+        let mut ram = [0; RAM_PAGES];
+        for (hash, page) in ram.iter_mut().zip(nes.ram().chunks(RAM_PAGE_SIZE)) {
+            *hash = crc32fast::hash(page);
+        }
+        FrameHash {
+            ram,
+            framebuffer: crc32fast::hash(&nes.framebuffer().data),
+        }
+    }
+}
+
+// The first frame that played out differently to when the movie was recorded
+#[derive(Debug)]
+pub struct Divergence {
+    pub frame: usize,
+    // Start address of every RAM page that differs
+    pub ram_pages: Vec<u16>,
+    pub framebuffer: bool,
+}
+
+impl Divergence {
+    fn new(frame: usize, expected: &FrameHash, actual: &FrameHash) -> Option<Self> {
+        let ram_pages: Vec<u16> = (0..RAM_PAGES)
+            .filter(|&page| expected.ram[page] != actual.ram[page])
+            .map(|page| (page * RAM_PAGE_SIZE) as u16)
+            .collect();
+        let framebuffer = expected.framebuffer != actual.framebuffer;
+
+        if ram_pages.is_empty() && !framebuffer {
+            return None;
+        }
+        Some(Divergence { frame, ram_pages, framebuffer })
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut differences: Vec<String> = self.ram_pages.iter()
+            .map(|&page| format!("RAM ${:04X}-${:04X}", page, page as usize + RAM_PAGE_SIZE - 1))
+            .collect();
+        if self.framebuffer {
+            differences.push("frame buffer".to_string());
+        }
+        write!(f, "Diverged at frame {}: {} differ", self.frame, differences.join(", "))
+    }
+}
+
+pub enum Playback {
+    Played,
+    Diverged(Divergence),
+    Finished,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Header {
+    magic: [u8; 4],
+    version: u16,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Movie {
+    pub rom_hash: u32,
+    // ROM file name without its extension, and the MD5 of its PRG and CHR ROM, for FM2 export
+    pub rom_name: String,
+    pub rom_md5: [u8; 16],
+    // Identifies the movie in FM2 files, as 32 hex digits in groups of 8-4-4-4-12
+    pub guid: String,
+    pub region: Region,
+    // How the machine is powered on, for movies starting from power-on
+    pub power_on_state: PowerOnState,
+    // Snapshot the movie starts from, or None to start from power-on
+    #[serde(with = "serde_bytes")]
+    pub start: Option<Vec<u8>>,
+    pub four_score: bool,
+    pub frames: Vec<MovieFrame>,
+    // Empty for imported movies, which have nothing to check against
+    pub hashes: Vec<FrameHash>,
+}
+
+impl Movie {
+    // Power cycles the machine so the recording starts from a known state
+    pub fn record_from_power_on(nes: &mut Nes) -> Self {
+        nes.power_cycle();
+        Movie::record(nes, None)
+    }
+
+    pub fn record_from_state(nes: &Nes) -> Self {
+        Movie::record(nes, Some(nes.snapshot()))
+    }
+
+    fn record(nes: &Nes, start: Option<Vec<u8>>) -> Self {
+        let cart = nes.cart();
+        Movie {
+            rom_hash: nes.rom_hash(),
+            rom_name: Path::new(&cart.filename).file_stem().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+            rom_md5: cart.md5(),
+            guid: new_guid(),
+            region: nes.region(),
+            power_on_state: nes.power_on_state(),
+            start,
+            four_score: nes.device(0).device_type() == DeviceType::FourScore,
+            frames: Vec::new(),
+            hashes: Vec::new(),
+        }
+    }
+
+    // Runs a frame with the buttons currently held, recording them and the state it ends in
+    pub fn record_frame(&mut self, nes: &mut Nes) {
+        self.frames.push(MovieFrame {
+            commands: 0,
+            players: [nes.buttons(0), nes.buttons(1), nes.buttons(2), nes.buttons(3)],
+        });
+        nes.run_frame();
+        self.hashes.push(FrameHash::of(nes));
+    }
+
+    // Puts the machine in the state the movie starts from
+    pub fn start(&self, nes: &mut Nes) -> Result<(), MovieError> {
+        if self.rom_hash != nes.rom_hash() {
+            return Err(MovieError::WrongRom { expected: nes.rom_hash(), found: self.rom_hash });
+        }
+        if self.four_score {
+            nes.connect(0, DeviceType::FourScore);
+        } else if nes.device(0).device_type() == DeviceType::FourScore {
+            nes.connect(0, DeviceType::Controller);
+        }
+        nes.set_region(self.region);
+        match &self.start {
+            Some(state) => nes.restore(state)?,
+            None => {
+                nes.set_power_on_state(self.power_on_state);
+                nes.power_cycle();
+            }
+        }
+        Ok(())
+    }
+
+    // Runs a frame with its recorded commands and input, then checks it ended the way it did when recorded
+    pub fn play_frame(&self, frame: usize, nes: &mut Nes) -> Playback {
+        let Some(input) = self.frames.get(frame) else {
+            return Playback::Finished;
+        };
+        if input.commands & COMMAND_POWER != 0 {
+            nes.power_cycle();
+        } else if input.commands & COMMAND_RESET != 0 {
+            nes.reset();
+        }
+        for (player, &buttons) in input.players.iter().enumerate() {
+            nes.set_buttons(player, buttons);
+        }
+        nes.run_frame();
+
+        let divergence = self.hashes.get(frame).and_then(|expected| Divergence::new(frame, expected, &FrameHash::of(nes)));
+        match divergence {
+            Some(divergence) => Playback::Diverged(divergence),
+            None => Playback::Played,
+        }
+    }
+
+    // Plays the whole movie, stopping at the first divergence. Returns the number of frames played
+    pub fn verify(&self, nes: &mut Nes) -> Result<usize, MovieError> {
+        self.start(nes)?;
+        for frame in 0..self.frames.len() {
+            if let Playback::Diverged(divergence) = self.play_frame(frame, nes) {
+                return Err(MovieError::Diverged(divergence));
+            }
+        }
+        Ok(self.frames.len())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = rmp_serde::to_vec(&Header { magic: MAGIC, version: FORMAT_VERSION }).unwrap();
+        data.extend(rmp_serde::to_vec(self).unwrap());
+        data
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, MovieError> {
+        let mut de = rmp_serde::Deserializer::new(data);
+
+        let header = Header::deserialize(&mut de).map_err(|_| MovieError::NotAMovie)?;
+        if header.magic != MAGIC {
+            return Err(MovieError::NotAMovie);
+        }
+        if header.version != FORMAT_VERSION {
+            return Err(MovieError::UnsupportedVersion(header.version));
+        }
+        Movie::deserialize(&mut de).map_err(|err| MovieError::Corrupt(err.to_string()))
+    }
+
+    // Paths ending in .fm2 are written as FM2
+    pub fn save(&self, path: &str) -> Result<(), MovieError> {
+        if is_fm2(path) {
+            fs::write(path, self.to_fm2()?)?;
+        } else {
+            fs::write(path, self.to_bytes())?;
+        }
+        Ok(())
+    }
+
+    // The ROM hash is only used for FM2 movies, others record their own
+    pub fn load(path: &str, rom_hash: u32) -> Result<Self, MovieError> {
+        if is_fm2(path) {
+            Movie::from_fm2(&fs::read_to_string(path)?, rom_hash)
+        } else {
+            Movie::from_bytes(&fs::read(path)?)
+        }
+    }
+
+    // Only movies starting from power-on can be exported, FCEUX can't read our save states
+    pub fn to_fm2(&self) -> Result<String, MovieError> {
+        if self.start.is_some() {
+            return Err(MovieError::Fm2("Movies starting from a save state can't be exported".to_string()));
+        }
+
+        // With a Four Score the ports are marked empty and every record holds all four controllers
+        let (four_score, ports, players) = if self.four_score { (1, 0, 4) } else { (0, 1, 2) };
+        let mut fm2 = format!(
+            "version 3\nemuVersion 0\nrerecordCount 0\npalFlag {}\nromFilename {}\nromChecksum base64:{}\nguid {}\nfourscore {}\nport0 {}\nport1 {}\nport2 0\n",
+            (self.region == Region::Pal) as u8, self.rom_name, BASE64.encode(self.rom_md5), self.guid, four_score, ports, ports,
+        );
+        for frame in &self.frames {
+            fm2.push_str(&format!("|{}|", frame.commands));
+            for &buttons in &frame.players[..players] {
+                fm2.push_str(&fm2_buttons(buttons));
+                fm2.push('|');
+            }
+            fm2.push_str("|\n");
+        }
+        Ok(fm2)
+    }
+
+    // FM2 movies don't record our ROM hash, so the caller vouches for which ROM it is for
+    pub fn from_fm2(fm2: &str, rom_hash: u32) -> Result<Self, MovieError> {
+        let mut frames = Vec::new();
+        let mut four_score = false;
+        let mut region = Region::Ntsc;
+        let mut rom_name = String::new();
+        let mut rom_md5 = [0; 16];
+        let mut guid = None;
+        for line in fm2.lines() {
+            if let Some(record) = line.strip_prefix('|') {
+                frames.push(parse_fm2_frame(record, four_score)?);
+                continue;
+            }
+
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match (key, value.trim()) {
+                ("savestate", _) => return Err(MovieError::Fm2("Movies starting from a save state are not supported".to_string())),
+                ("fourscore", value) => four_score = value == "1",
+                ("palFlag", value) => region = if value == "1" { Region::Pal } else { Region::Ntsc },
+                ("romFilename", name) => rom_name = name.to_string(),
+                ("romChecksum", checksum) => {
+                    rom_md5 = checksum.strip_prefix("base64:")
+                        .and_then(|checksum| BASE64.decode(checksum).ok())
+                        .and_then(|md5| md5.try_into().ok())
+                        .ok_or_else(|| MovieError::Fm2(format!("Invalid romChecksum {}", checksum)))?;
+                }
+                ("guid", value) => guid = Some(value.to_string()),
+                ("port0" | "port1", port) if port != "0" && port != "1" => {
+                    return Err(MovieError::Fm2(format!("Unsupported device {} on {}", port, key)));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Movie {
+            rom_hash,
+            rom_name,
+            rom_md5,
+            guid: guid.unwrap_or_else(new_guid),
+            region,
+            power_on_state: PowerOnState::Fceux,
+            start: None,
+            four_score,
+            frames,
+            hashes: Vec::new(),
+        })
+    }
+}
+
+fn is_fm2(path: &str) -> bool {
+    path.to_ascii_lowercase().ends_with(".fm2")
+}
+
+fn new_guid() -> String {
+    let bytes: [u8; 16] = rand::random();
+    let hex: String = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+fn fm2_buttons(mask: u8) -> String {
+    FM2_BUTTONS.iter().enumerate()
+        .map(|(i, &button)| if mask & (0x80 >> i) != 0 { button as char } else { '.' })
+        .collect()
+}
+
+/*
+    Record lines look like |commands|port0|port1|port2|, or |commands|p1|p2|p3|p4|port2| with a Four Score.
+    Anything but a space or . is a pressed button.
+*/
+fn parse_fm2_frame(record: &str, four_score: bool) -> Result<MovieFrame, MovieError> {
+    let mut fields = record.split('|');
+    let commands = fields.next().unwrap_or("").trim();
+    let commands = if commands.is_empty() { 0 } else {
+        commands.parse().map_err(|_| MovieError::Fm2(format!("Invalid commands {:?}", commands)))?
+    };
+
+    let mut players = [0; 4];
+    let count = if four_score { 4 } else { 2 };
+    for player in players[..count].iter_mut() {
+        let buttons = fields.next().unwrap_or("");
+        if buttons.is_empty() {
+            continue;
+        }
+        if buttons.len() != 8 {
+            return Err(MovieError::Fm2(format!("Invalid controller input {:?}", buttons)));
+        }
+        for (i, button) in buttons.bytes().enumerate() {
+            if button != b'.' && button != b' ' {
+                *player |= 0x80 >> i;
+            }
+        }
+    }
+
+    Ok(MovieFrame { commands, players })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{nestest, nestest_cart};
+
+    fn record(nes: &mut Nes) -> Movie {
+        let mut movie = Movie::record_from_power_on(nes);
+        for frame in 0..30u8 {
+            nes.set_buttons(0, frame.wrapping_mul(37));
+            movie.record_frame(nes);
+        }
+        movie
+    }
+
+    #[test]
+    fn test_record_and_play() {
+        let mut nes = Nes::with_seed(nestest_cart(), 7);
+        let movie = Movie::from_bytes(&record(&mut nes).to_bytes()).unwrap();
+
+        let mut replay = nestest();
+        replay.run_frame();
+        assert_eq!(movie.verify(&mut replay).unwrap(), 30);
+        assert_eq!(replay.snapshot(), nes.snapshot());
+    }
+
+    #[test]
+    fn test_divergence() {
+        let mut movie = record(&mut nestest());
+        movie.frames[10].players[0] ^= 0b0000_1000;
+
+        let Err(MovieError::Diverged(divergence)) = movie.verify(&mut nestest()) else {
+            panic!("Expected the movie to diverge");
+        };
+        assert!(divergence.frame >= 10);
+        assert!(!divergence.ram_pages.is_empty() || divergence.framebuffer);
+    }
+
+    #[test]
+    fn test_fm2() {
+        let fm2 = "version 3\nport0 1\nport1 1\nport2 0\n|0|R......A|........||\n|2|...U.S..|.L......||\n|0|||||\n";
+        let movie = Movie::from_fm2(fm2, 0).unwrap();
+        assert_eq!(movie.frames, [
+            MovieFrame { commands: 0, players: [0b1000_0001, 0, 0, 0] },
+            MovieFrame { commands: COMMAND_POWER, players: [0b0001_0100, 0b0100_0000, 0, 0] },
+            MovieFrame { commands: 0, players: [0, 0, 0, 0] },
+        ]);
+
+        let exported = Movie::from_fm2(&movie.to_fm2().unwrap(), 0).unwrap();
+        assert_eq!(exported.frames, movie.frames);
+        assert_eq!(exported.guid, movie.guid);
+        assert!(Movie::from_fm2("port0 2\n", 0).is_err());
+
+        // Imported movies power on the way FCEUX does, whatever the machine was seeded with
+        let mut nes = Nes::with_seed(nestest_cart(), 7);
+        Movie::from_fm2(fm2, nes.rom_hash()).unwrap().start(&mut nes).unwrap();
+        assert_eq!(nes.power_on_state(), PowerOnState::Fceux);
+        assert_eq!(nes.ram()[..12], [0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_fm2_header() {
+        let mut nes = nestest();
+        nes.set_region(Region::Pal);
+        let movie = record(&mut nes);
+        let fm2 = movie.to_fm2().unwrap();
+        let header: Vec<&str> = fm2.lines().take_while(|line| !line.starts_with('|')).collect();
+        assert!(header.contains(&"palFlag 1"));
+        assert!(header.contains(&"romFilename nestest"));
+        assert!(header.contains(&format!("romChecksum base64:{}", BASE64.encode(nes.cart().md5())).as_str()));
+        assert!(header.iter().any(|line| line.strip_prefix("guid ").is_some_and(|guid| guid.len() == 36)));
+
+        let imported = Movie::from_fm2(&fm2, nes.rom_hash()).unwrap();
+        assert_eq!(imported.region, Region::Pal);
+        assert_eq!(imported.rom_name, "nestest");
+        assert_eq!(imported.rom_md5, nes.cart().md5());
+        assert_eq!(imported.guid, movie.guid);
+        assert!(Movie::from_fm2("romChecksum 1234\n", 0).is_err());
+    }
+
+    #[test]
+    fn test_corrupt_movie() {
+        let data = record(&mut nestest()).to_bytes();
+        assert!(matches!(Movie::from_bytes(&data[..data.len() / 2]), Err(MovieError::Corrupt(_))));
+        assert!(matches!(Movie::from_bytes(b"not a movie"), Err(MovieError::NotAMovie)));
+    }
+
+    #[test]
+    fn test_fm2_four_score() {
+        let fm2 = "version 3\nfourscore 1\nport0 0\nport1 0\nport2 0\n|0|R.......|.L......|..D.....|...U....||\n";
+        let movie = Movie::from_fm2(fm2, 0).unwrap();
+        assert!(movie.four_score);
+        assert_eq!(movie.frames[0].players, [0b1000_0000, 0b0100_0000, 0b0010_0000, 0b0001_0000]);
+        assert_eq!(movie.to_fm2().unwrap().lines().last(), Some("|0|R.......|.L......|..D.....|...U....||"));
+    }
+}
@@ -0,0 +1,361 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::bus::{Bus, Memory};
+use crate::cart::{Cart, CartError, Region};
+use crate::cpu::CPU;
+use crate::devices::{Device, DeviceType};
+use crate::joypad::{Inputs, Joypad};
+use crate::rendering::{Frame, Palette, SYSTEM_PALLETE, render};
+use crate::state::{State, StateError};
+
+// How work RAM and the PPU come up when the machine is powered on
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum PowerOnState {
+    // Leftover values from a seeded random generator, runs with the same seed and input are identical
+    Random(u64),
+    // The pattern FCEUX powers on with, so its movies play back the same way
+    Fceux,
+}
+
+// Players 3 and 4 need a Four Score
+pub const PLAYERS: usize = 4;
+
+// Headless machine, drives the CPU a frame at a time without needing a window
+pub struct Nes {
+    cart: Cart,
+    rom_hash: u32,
+    power_on_state: PowerOnState,
+    devices: [DeviceType; 2],
+    region: Region,
+    cpu: CPU<Bus>,
+    frame: Frame,
+    palette: Palette,
+}
+
+impl Nes {
+    pub fn new(cart: Cart) -> Self {
+        Nes::with_seed(cart, 0)
+    }
+
+    pub fn with_seed(cart: Cart, seed: u64) -> Self {
+        Nes::with_power_on_state(cart, PowerOnState::Random(seed))
+    }
+
+    pub fn with_power_on_state(cart: Cart, power_on_state: PowerOnState) -> Self {
+        let devices = Nes::default_devices(&cart);
+        let region = cart.rom_header.region;
+        let mut nes = Nes {
+            cpu: Nes::power_on(cart.clone(), power_on_state, devices, region),
+            rom_hash: cart.crc32(),
+            power_on_state,
+            devices,
+            region,
+            cart,
+            frame: Frame::new(),
+            palette: SYSTEM_PALLETE,
+        };
+        nes.cpu.reset();
+        nes
+    }
+
+    fn power_on(cart: Cart, power_on_state: PowerOnState, devices: [DeviceType; 2], region: Region) -> CPU<Bus> {
+        let mut bus = Bus::new(cart);
+        bus.set_region(region);
+        match power_on_state {
+            PowerOnState::Random(seed) => bus.power_on(&mut StdRng::seed_from_u64(seed)),
+            PowerOnState::Fceux => bus.power_on_fceux(),
+        }
+        for (port, &device) in devices.iter().enumerate() {
+            bus.connect(port, Device::new(device, port));
+        }
+        CPU::new(bus)
+    }
+
+    // Whatever the header asks for, otherwise a controller in each port
+    fn default_devices(cart: &Cart) -> [DeviceType; 2] {
+        DeviceType::from_expansion_device(cart.rom_header.expansion_device)
+            .unwrap_or([DeviceType::Controller, DeviceType::Controller])
+    }
+
+    pub fn power_on_state(&self) -> PowerOnState {
+        self.power_on_state
+    }
+
+    // Takes effect from the next power cycle
+    pub fn set_power_on_state(&mut self, power_on_state: PowerOnState) {
+        self.power_on_state = power_on_state;
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    // Overrides the region from the header, takes effect from the next power cycle
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+    }
+
+    // Colours for each of the 64 NES palette entries, used from the next frame
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    // Swaps in another ROM and powers on, the current one is kept if it can't be loaded
+    pub fn load_rom(&mut self, path: &str) -> Result<(), CartError> {
+        self.cart = Cart::load(path)?;
+        self.rom_hash = self.cart.crc32();
+        self.devices = Nes::default_devices(&self.cart);
+        self.region = self.cart.rom_header.region;
+        self.power_cycle();
+        Ok(())
+    }
+
+    // Executes a single instruction, servicing a pending NMI first
+    pub fn step_instruction(&mut self) {
+        if let Some(_nmi) = self.cpu.bus.poll_nmi_status() {
+            self.cpu.interrupt_nmi();
+        }
+        self.cpu.step();
+    }
+
+    // Runs until the PPU enters vblank and renders the finished frame
+    pub fn run_frame(&mut self) {
+        while !self.cpu.bus.poll_frame_complete() {
+            self.step_instruction();
+        }
+        self.render();
+    }
+
+    // Redraws the frame buffer from the PPU, e.g. after restoring a state taken at the end of a frame
+    pub fn render(&mut self) {
+        render(self.cpu.bus.ppu(), &self.palette, &mut self.frame);
+    }
+
+    pub fn framebuffer(&self) -> &Frame {
+        &self.frame
+    }
+
+    // Bit 0 is A through to bit 7 for Right, matching the order the buttons are read from $4016.
+    // Ignored unless the player has a controller, players 3 and 4 need a Four Score.
+    pub fn set_buttons(&mut self, player: usize, mask: u8) {
+        if let Some(joypad) = self.joypad_mut(player) {
+            joypad.set_buttons(mask);
+        }
+    }
+
+    pub fn set_button_pressed_status(&mut self, player: usize, button: Inputs, pressed: bool) {
+        if let Some(joypad) = self.joypad_mut(player) {
+            joypad.set_button_pressed_status(button, pressed);
+        }
+    }
+
+    pub fn buttons(&self, player: usize) -> u8 {
+        self.joypad(player).map_or(0, Joypad::buttons)
+    }
+
+    pub fn joypad(&self, player: usize) -> Option<&Joypad> {
+        self.cpu.bus.joypad(player)
+    }
+
+    pub fn joypad_mut(&mut self, player: usize) -> Option<&mut Joypad> {
+        self.cpu.bus.joypad_mut(player)
+    }
+
+    // Plugs a new device into a port, it stays connected across power cycles. The Four Score takes up
+    // both ports, so connecting it or replacing it changes the other port too.
+    pub fn connect(&mut self, port: usize, device: DeviceType) {
+        let other = 1 - port;
+        if device == DeviceType::FourScore {
+            self.devices[other] = DeviceType::FourScore;
+        } else if self.devices[other] == DeviceType::FourScore {
+            self.devices[other] = DeviceType::Controller;
+        }
+        self.devices[port] = device;
+
+        for (port, &device) in self.devices.iter().enumerate() {
+            if self.cpu.bus.port(port).device_type() != device {
+                self.cpu.bus.connect(port, Device::new(device, port));
+            }
+        }
+    }
+
+    pub fn cart(&self) -> &Cart {
+        &self.cart
+    }
+
+    pub fn device(&self, port: usize) -> &Device {
+        self.cpu.bus.port(port)
+    }
+
+    pub fn device_mut(&mut self, port: usize) -> &mut Device {
+        self.cpu.bus.port_mut(port)
+    }
+
+    pub fn ram(&self) -> &[u8; 2048] {
+        self.cpu.bus.ram()
+    }
+
+    // CRC32 of the loaded ROM, save states record it so they are only loaded back into the same game
+    pub fn rom_hash(&self) -> u32 {
+        self.rom_hash
+    }
+
+    pub fn state(&self) -> State {
+        self.cpu.bus.state(self.cpu.regs.clone())
+    }
+
+    pub fn set_state(&mut self, state: State) {
+        self.devices = [state.ports[0].device_type(), state.ports[1].device_type()];
+        self.cpu.regs = self.cpu.bus.set_state(state);
+    }
+
+    // In-memory save state, never touches the filesystem and is cheap enough to take every frame
+    pub fn snapshot(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        self.snapshot_into(&mut data);
+        data
+    }
+
+    // Same as snapshot but overwrites an existing buffer, so taking snapshots repeatedly doesn't allocate
+    pub fn snapshot_into(&self, buf: &mut Vec<u8>) {
+        self.cpu.bus.state_ref(&self.cpu.regs).write_to(self.rom_hash, buf);
+    }
+
+    // Snapshots taken while running a different ROM, or by an incompatible version, are rejected
+    pub fn restore(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let state = State::from_bytes(data, self.rom_hash)?;
+        self.set_state(state);
+        Ok(())
+    }
+
+    pub fn reset(&mut self) {
+        self.cpu.reset();
+    }
+
+    pub fn power_cycle(&mut self) {
+        self.cpu = Nes::power_on(self.cart.clone(), self.power_on_state, self.devices, self.region);
+        // Cleared in place so views of the frame buffer stay valid
+        self.frame.data.fill(0);
+        self.cpu.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::nestest;
+
+    #[test]
+    fn test_run_frame() {
+        let mut nes = nestest();
+        for _ in 0..10 {
+            nes.run_frame();
+        }
+        assert!(nes.framebuffer().data.iter().any(|&p| p != 0));
+    }
+
+    #[test]
+    fn test_run_frame_through_brk() {
+        // PRG ROM full of BRKs, with every vector pointing back at the start
+        let mut rom = vec![0x4E, 0x45, 0x53, 0x1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let mut prg_rom = vec![0x00; 0x4000];
+        prg_rom[0x3FFA..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x00, 0x80]);
+        rom.extend(prg_rom);
+        rom.extend([0x00; 0x2000]);
+
+        let mut nes = Nes::new(Cart::from_bytes("brk.nes", rom).unwrap());
+        nes.run_frame();
+        let start = nes.cpu.bus.cycles();
+        nes.run_frame();
+        assert!((nes.cpu.bus.cycles() - start).abs_diff(29781) < 8);
+    }
+
+    #[test]
+    fn test_set_buttons() {
+        let mut nes = nestest();
+        nes.set_buttons(0, 0b1000_0001);
+        nes.set_buttons(1, 0b0100_0010);
+
+        let bus = &mut nes.cpu.bus;
+        bus.write(0x4016, 1);
+        bus.write(0x4016, 0);
+        let buttons: Vec<u8> = (0..8).map(|_| bus.read(0x4016)).collect();
+        assert_eq!(buttons, [1, 0, 0, 0, 0, 0, 0, 1]);
+        let buttons: Vec<u8> = (0..8).map(|_| bus.read(0x4017)).collect();
+        assert_eq!(buttons, [0, 1, 0, 0, 0, 0, 1, 0]);
+    }
+
+    #[test]
+    fn test_connect() {
+        let mut nes = nestest();
+        nes.connect(0, DeviceType::FourScore);
+        nes.set_buttons(3, 0b0000_0001);
+        assert_eq!(nes.device(1).device_type(), DeviceType::FourScore);
+        assert_eq!(nes.buttons(3), 1);
+
+        nes.connect(1, DeviceType::Zapper);
+        assert_eq!(nes.device(0).device_type(), DeviceType::Controller);
+        if let Device::Zapper(zapper) = nes.device_mut(1) {
+            // Aimed off screen so it never sees light
+            zapper.aim(crate::WINDOW_WIDTH, 0);
+            zapper.trigger = true;
+        }
+        assert_eq!(nes.cpu.bus.read(0x4017), 0b0001_1000);
+
+        nes.power_cycle();
+        assert_eq!(nes.device(1).device_type(), DeviceType::Zapper);
+        assert_eq!(nes.buttons(1), 0);
+    }
+
+    #[test]
+    fn test_region() {
+        let cycles_per_frame = |nes: &mut Nes| {
+            nes.run_frame();
+            let start = nes.cpu.bus.cycles();
+            nes.run_frame();
+            nes.cpu.bus.cycles() - start
+        };
+        let mut nes = nestest();
+        assert_eq!(nes.region(), Region::Ntsc);
+        // 262 scanlines of 341 PPU cycles, at 3 PPU cycles per CPU cycle
+        assert!(cycles_per_frame(&mut nes).abs_diff(29781) < 8);
+
+        nes.set_region(Region::Pal);
+        nes.power_cycle();
+        // 312 scanlines at 3.2 PPU cycles per CPU cycle
+        assert!(cycles_per_frame(&mut nes).abs_diff(33248) < 8);
+    }
+
+    #[test]
+    fn test_power_cycle() {
+        let mut nes = nestest();
+        nes.run_frame();
+        nes.cpu.bus.write(0x0000, 0xAB);
+        nes.power_cycle();
+        assert_eq!(nes.ram(), nestest().ram());
+        assert_eq!(nes.snapshot(), nestest().snapshot());
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut nes = nestest();
+        for _ in 0..5 {
+            nes.run_frame();
+        }
+        let snapshot = nes.snapshot();
+        assert_eq!(snapshot, nes.state().to_bytes(nes.rom_hash()));
+
+        let mut restored = nestest();
+        restored.restore(&snapshot).unwrap();
+        assert_eq!(restored.cpu.bus.ppu().chr_rom, nes.cart().chr_rom);
+        for _ in 0..5 {
+            nes.run_frame();
+            restored.run_frame();
+        }
+        assert_eq!(nes.ram(), restored.ram());
+        assert_eq!(nes.cpu.regs.pc, restored.cpu.regs.pc);
+        assert_eq!(nes.cpu.bus.cycles(), restored.cpu.bus.cycles());
+        assert_eq!(nes.framebuffer().data, restored.framebuffer().data);
+    }
+}
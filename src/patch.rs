@@ -0,0 +1,374 @@
+/*
+    Soft patches, applied to a ROM in memory as it is loaded so patched copies don't need to be kept
+    around. Patches are applied to the whole file, header included, as they are made that way.
+
+    IPS patches are a list of bytes to overwrite. UPS and BPS patches also end with the CRC32s of the
+    ROM they were made for, of the patched ROM and of the patch itself, which are all checked.
+*/
+use std::path::{Path, PathBuf};
+use std::{fmt, fs, io};
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+const UPS_MAGIC: &[u8] = b"UPS1";
+const BPS_MAGIC: &[u8] = b"BPS1";
+// Source, target and patch CRC32s
+const FOOTER_SIZE: usize = 12;
+// Largest patched ROM accepted, far beyond any real cartridge, so a bad size can't exhaust memory
+const MAX_TARGET_SIZE: usize = 64 * 1024 * 1024;
+
+// Looked for next to a ROM, in this order
+const PATCH_EXTENSIONS: [&str; 3] = ["ips", "ups", "bps"];
+
+#[derive(Debug)]
+pub enum PatchError {
+    Io(io::Error),
+    UnknownFormat,
+    Malformed,
+    // The patch reads or writes past the end of the ROM
+    OutOfBounds,
+    WrongSize { expected: usize, found: usize },
+    SourceChecksum { expected: u32, found: u32 },
+    TargetChecksum { expected: u32, found: u32 },
+    PatchChecksum { expected: u32, found: u32 },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatchError::Io(err) => write!(f, "{}", err),
+            PatchError::UnknownFormat => write!(f, "Not an IPS, UPS or BPS patch"),
+            PatchError::Malformed => write!(f, "Patch is truncated or malformed"),
+            PatchError::OutOfBounds => write!(f, "Patch goes past the end of the ROM"),
+            PatchError::WrongSize { expected, found } => write!(f, "Patch is for a ROM of {} bytes, this one is {}", expected, found),
+            PatchError::SourceChecksum { expected, found } => write!(f, "Patch is for ROM {:08X}, this one is {:08X}", expected, found),
+            PatchError::TargetChecksum { expected, found } => write!(f, "Patched ROM is {:08X}, expected {:08X}", found, expected),
+            PatchError::PatchChecksum { expected, found } => write!(f, "Patch is corrupt, its CRC32 is {:08X}, expected {:08X}", found, expected),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+impl From<io::Error> for PatchError {
+    fn from(err: io::Error) -> Self {
+        PatchError::Io(err)
+    }
+}
+
+// A patch next to the ROM with the same name, with the ROM's extension replaced or kept, e.g. game.ips or game.nes.ips
+pub fn find(rom: &Path) -> Option<PathBuf> {
+    PATCH_EXTENSIONS.iter()
+        .flat_map(|extension| {
+            let mut kept = rom.as_os_str().to_owned();
+            kept.push(".");
+            kept.push(extension);
+            [rom.with_extension(extension), PathBuf::from(kept)]
+        })
+        .find(|path| path.is_file())
+}
+
+pub fn apply_file(path: &Path, rom: &[u8]) -> Result<Vec<u8>, PatchError> {
+    apply(&fs::read(path)?, rom)
+}
+
+// Applies a patch of any of the formats, told apart by their magic bytes
+pub fn apply(patch: &[u8], rom: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if let Some(records) = patch.strip_prefix(IPS_MAGIC) {
+        return apply_ips(records, rom);
+    }
+    let apply_checked = if patch.starts_with(UPS_MAGIC) {
+        apply_ups
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps
+    } else {
+        return Err(PatchError::UnknownFormat);
+    };
+
+    if patch.len() < UPS_MAGIC.len() + FOOTER_SIZE {
+        return Err(PatchError::Malformed);
+    }
+    let (body, footer) = patch.split_at(patch.len() - FOOTER_SIZE);
+    let checksum = |index: usize| u32::from_le_bytes(footer[index * 4..index * 4 + 4].try_into().unwrap());
+    // The patch's own CRC32 covers everything before it
+    let found = crc32fast::hash(&patch[..patch.len() - 4]);
+    if found != checksum(2) {
+        return Err(PatchError::PatchChecksum { expected: checksum(2), found });
+    }
+    let found = crc32fast::hash(rom);
+    if found != checksum(0) {
+        return Err(PatchError::SourceChecksum { expected: checksum(0), found });
+    }
+
+    let target = apply_checked(&body[UPS_MAGIC.len()..], rom)?;
+    let found = crc32fast::hash(&target);
+    if found != checksum(1) {
+        return Err(PatchError::TargetChecksum { expected: checksum(1), found });
+    }
+    Ok(target)
+}
+
+/*
+    Records of a 3 byte offset and a 2 byte length followed by that many bytes, or by a 2 byte count and
+    a byte to repeat when the length is 0. Records can write past the end of the ROM to make it bigger,
+    and some patches give a size to cut the ROM down to after the end marker.
+*/
+fn apply_ips(records: &[u8], rom: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let mut reader = Reader::new(records);
+    let mut target = rom.to_vec();
+    loop {
+        let offset = reader.bytes(3)?;
+        if offset == IPS_EOF {
+            break;
+        }
+        let offset = big_endian(offset);
+        let (length, run) = match big_endian(reader.bytes(2)?) {
+            0 => (big_endian(reader.bytes(2)?), Some(reader.byte()?)),
+            length => (length, None),
+        };
+        if target.len() < offset + length {
+            target.resize(offset + length, 0);
+        }
+        match run {
+            Some(value) => target[offset..offset + length].fill(value),
+            None => target[offset..offset + length].copy_from_slice(reader.bytes(length)?),
+        }
+    }
+    if let Ok(size) = reader.bytes(3) {
+        target.truncate(big_endian(size));
+    }
+    Ok(target)
+}
+
+/*
+    The sizes of the ROM and the patched ROM, then hunks of a distance to skip and bytes to XOR with
+    the ROM, ending at a 0 byte.
+*/
+fn apply_ups(body: &[u8], rom: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let mut reader = Reader::new(body);
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+    if target_size > MAX_TARGET_SIZE {
+        return Err(PatchError::Malformed);
+    }
+    if source_size != rom.len() {
+        return Err(PatchError::WrongSize { expected: source_size, found: rom.len() });
+    }
+
+    let mut target = rom.to_vec();
+    target.resize(target_size, 0);
+    let mut offset = 0;
+    while !reader.is_empty() {
+        offset = reader.number()?.checked_add(offset).ok_or(PatchError::Malformed)?;
+        loop {
+            let xor = reader.byte()?;
+            if xor == 0 {
+                offset += 1;
+                break;
+            }
+            *target.get_mut(offset).ok_or(PatchError::OutOfBounds)? ^= xor;
+            offset += 1;
+        }
+    }
+    Ok(target)
+}
+
+/*
+    The sizes of the ROM and the patched ROM and some metadata, then actions that build the patched ROM
+    from the start. Each copies a run of bytes from the same place in the ROM, from the patch, or from
+    anywhere in the ROM or what has been built so far, relative to where the last copy of that kind ended.
+*/
+fn apply_bps(body: &[u8], rom: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let mut reader = Reader::new(body);
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+    if target_size > MAX_TARGET_SIZE {
+        return Err(PatchError::Malformed);
+    }
+    let metadata_size = reader.number()?;
+    reader.bytes(metadata_size)?;
+    if source_size != rom.len() {
+        return Err(PatchError::WrongSize { expected: source_size, found: rom.len() });
+    }
+
+    let mut target = Vec::with_capacity(target_size);
+    let mut source_offset = 0;
+    let mut target_offset = 0;
+    while !reader.is_empty() {
+        let action = reader.number()?;
+        let length = (action >> 2) + 1;
+        match action & 0b11 {
+            0 => {
+                let start = target.len();
+                target.extend_from_slice(rom.get(start..start + length).ok_or(PatchError::OutOfBounds)?);
+            }
+            1 => target.extend_from_slice(reader.bytes(length)?),
+            2 => {
+                source_offset = relative(source_offset, reader.number()?)?;
+                target.extend_from_slice(rom.get(source_offset..source_offset + length).ok_or(PatchError::OutOfBounds)?);
+                source_offset += length;
+            }
+            _ => {
+                target_offset = relative(target_offset, reader.number()?)?;
+                // The copy can overlap what it is writing, which repeats a pattern
+                for _ in 0..length {
+                    let byte = *target.get(target_offset).ok_or(PatchError::OutOfBounds)?;
+                    target.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+        if target.len() > target_size {
+            return Err(PatchError::OutOfBounds);
+        }
+    }
+    if target.len() != target_size {
+        return Err(PatchError::Malformed);
+    }
+    Ok(target)
+}
+
+// BPS offsets are a distance with the direction in the lowest bit
+fn relative(offset: usize, data: usize) -> Result<usize, PatchError> {
+    let distance = data >> 1;
+    let offset = if data & 1 == 1 { offset.checked_sub(distance) } else { offset.checked_add(distance) };
+    offset.ok_or(PatchError::OutOfBounds)
+}
+
+fn big_endian(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |value, &byte| value << 8 | byte as usize)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data, position: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], PatchError> {
+        let bytes = self.data.get(self.position..self.position.saturating_add(length)).ok_or(PatchError::Malformed)?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, PatchError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    /*
+        UPS and BPS numbers, 7 bits at a time from the lowest with the top bit set on the last byte.
+        Each continuation also adds one, so every number has exactly one encoding.
+    */
+    fn number(&mut self) -> Result<usize, PatchError> {
+        let mut value: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let byte = self.byte()?;
+            value = ((byte & 0x7F) as usize).checked_mul(shift).and_then(|bits| value.checked_add(bits)).ok_or(PatchError::Malformed)?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.checked_mul(0x80).ok_or(PatchError::Malformed)?;
+            value = value.checked_add(shift).ok_or(PatchError::Malformed)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(mut value: usize, patch: &mut Vec<u8>) {
+        loop {
+            let bits = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                patch.push(bits | 0x80);
+                return;
+            }
+            patch.push(bits);
+            value -= 1;
+        }
+    }
+
+    fn with_footer(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+        patch.extend(crc32fast::hash(source).to_le_bytes());
+        patch.extend(crc32fast::hash(target).to_le_bytes());
+        patch.extend(crc32fast::hash(&patch).to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn test_patches() {
+        let rom: Vec<u8> = (0..200).collect();
+
+        // Overwrite 2 bytes, grow the ROM with a run of 300 0xAA, then cut it back to 250 bytes
+        let mut ips = b"PATCH".to_vec();
+        ips.extend([0, 0, 1, 0, 2, 0xFF, 0xFE]);
+        ips.extend([0, 0, 150, 0, 0, 1, 0x2C, 0xAA]);
+        ips.extend(b"EOF");
+        ips.extend([0, 0, 250]);
+        let target = apply(&ips, &rom).unwrap();
+        assert_eq!(target.len(), 250);
+        assert_eq!(&target[..4], [0, 0xFF, 0xFE, 3]);
+        assert!(target[150..].iter().all(|&byte| byte == 0xAA));
+
+        // Flip byte 130 and add 2 bytes on the end, the 0 ending a hunk counts as a byte too
+        let mut expected = rom.clone();
+        expected[130] ^= 0x0F;
+        expected.extend([0x12, 0x34]);
+        let mut ups = b"UPS1".to_vec();
+        number(rom.len(), &mut ups);
+        number(expected.len(), &mut ups);
+        number(130, &mut ups);
+        ups.extend([0x0F, 0]);
+        number(200 - 132, &mut ups);
+        ups.extend([0x12, 0x34, 0]);
+        let ups = with_footer(ups, &rom, &expected);
+        assert_eq!(apply(&ups, &rom).unwrap(), expected);
+
+        // Keep the first 10 bytes, write 2 new ones, repeat them twice more and copy 4 from byte 100
+        let expected = [&rom[..10], &[1, 2, 1, 2, 1, 2], &rom[100..104]].concat();
+        let mut bps = b"BPS1".to_vec();
+        number(rom.len(), &mut bps);
+        number(expected.len(), &mut bps);
+        number(2, &mut bps);
+        bps.extend(b"{}");
+        number(9 << 2, &mut bps);
+        number(1 << 2 | 1, &mut bps);
+        bps.extend([1, 2]);
+        number(3 << 2 | 3, &mut bps);
+        number(10 << 1, &mut bps);
+        number(3 << 2 | 2, &mut bps);
+        number(100 << 1, &mut bps);
+        let bps = with_footer(bps, &rom, &expected);
+        assert_eq!(apply(&bps, &rom).unwrap(), expected);
+
+        // Checksums catch the wrong ROM and a damaged patch
+        let mut other = rom.clone();
+        other[0] = 1;
+        assert!(matches!(apply(&bps, &other), Err(PatchError::SourceChecksum { .. })));
+        let mut damaged = ups.clone();
+        damaged[8] ^= 1;
+        assert!(matches!(apply(&damaged, &rom), Err(PatchError::PatchChecksum { .. })));
+        assert!(matches!(apply(b"NES\x1A", &rom), Err(PatchError::UnknownFormat)));
+
+        // A huge patched ROM is refused before anything is allocated, even with valid checksums
+        for magic in [b"UPS1", b"BPS1"] {
+            let mut huge = magic.to_vec();
+            number(rom.len(), &mut huge);
+            number(usize::MAX >> 8, &mut huge);
+            number(0, &mut huge);
+            let huge = with_footer(huge, &rom, &[]);
+            assert!(matches!(apply(&huge, &rom), Err(PatchError::Malformed)));
+        }
+    }
+}
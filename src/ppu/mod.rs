@@ -1,12 +1,13 @@
-use crate::cart::Mirroring;
+use crate::cart::{Mirroring, Region};
 
 use registers::addr::AddrRegister;
 use registers::scroll::ScrollRegister;
-use serde_big_array::BigArray;
 
 pub mod registers;
 #[derive(Clone, Deserialize, Serialize)]
 pub struct PPU {
+    // Left out of save states, it comes from the cartridge and restoring keeps the loaded one's
+    #[serde(skip)]
     pub chr_rom: Vec<u8>,
     pub mirroring: Mirroring,
     pub ctrl: u8,
@@ -14,17 +15,21 @@ pub struct PPU {
     pub status: u8,
     pub scroll: ScrollRegister,
     pub addr: AddrRegister,
-    #[serde(with = "BigArray")]
+    #[serde(with = "serde_bytes")]
     pub vram: [u8; 2048],
 
     pub oam_addr: u8,
-    #[serde(with = "BigArray")]
+    #[serde(with = "serde_bytes")]
     pub oam_data: [u8; 256],
+    #[serde(with = "serde_bytes")]
     pub palette_table: [u8; 32],
   
     internal_data_buffer: u8,
     scanline: u16,
     cycles: usize,
+    region: Region,
+    // Fifths of a PPU cycle carried over, PAL runs 3.2 PPU cycles per CPU cycle
+    clock_remainder: u8,
 
     pub nmi_interrupt: Option<u8>, 
 }
@@ -46,6 +51,8 @@ impl PPU {
             oam_addr: 0,
             scanline: 0,
             cycles: 0,
+            region: Region::Ntsc,
+            clock_remainder: 0,
             nmi_interrupt: None,
         }
     }
@@ -170,6 +177,37 @@ impl PPU {
         }
     }
 
+    pub fn scanline(&self) -> u16 {
+        self.scanline
+    }
+
+    // Where in the frame the PPU is when the CPU starts running, it varies from one power-on to the next
+    pub(crate) fn set_position(&mut self, scanline: u16, cycles: usize) {
+        self.scanline = scanline;
+        self.cycles = cycles;
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    pub(crate) fn set_region(&mut self, region: Region) {
+        self.region = region;
+    }
+
+    // PPU cycles that pass during a number of CPU cycles
+    pub fn ppu_cycles(&mut self, cpu_cycles: u8) -> u8 {
+        match self.region {
+            Region::Ntsc => cpu_cycles * 3,
+            Region::Pal => {
+                let fifths = cpu_cycles as u16 * 16 + self.clock_remainder as u16;
+                self.clock_remainder = (fifths % 5) as u8;
+                (fifths / 5) as u8
+            }
+        }
+    }
+
+    // Returns true once the visible frame is finished and vblank starts
     pub fn tick(&mut self, cycles: u8) -> bool {
         self.cycles += cycles as usize;
         // Scanlines last for 341 PPU clock cycles
@@ -187,14 +225,14 @@ impl PPU {
                 if self.generate_vblank_nmi() {
                     self.nmi_interrupt = Some(1);
                 }
+                return true;
             }
 
-            if self.scanline >= 262 {
+            if self.scanline >= self.region.scanlines() {
                 self.scanline = 0;
                 self.nmi_interrupt = None;
                 self.set_sprite_zero_hit(false);
                 self.reset_vblank_status();
-                return true;
             }
         }
         false
@@ -1,4 +1,4 @@
-use crate::lib::get_bit;
+use crate::get_bit;
 use crate::ppu::PPU;
 
 #[allow(dead_code)]
@@ -1,5 +1,5 @@
 use crate::ppu::PPU;
-use crate::lib::get_bit;
+use crate::get_bit;
 
 enum MaskFlags {
     Greyscale = 0,
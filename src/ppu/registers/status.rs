@@ -1,4 +1,4 @@
-use crate::lib::{get_bit, set_bit};
+use crate::{get_bit, set_bit};
 use crate::ppu::PPU;
 
 enum StatusFlags {
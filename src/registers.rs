@@ -9,8 +9,8 @@ pub enum CPUStatusFlags {
     NegativeResult = 7,
 }
 
-#[derive(Default)]
-pub(crate) struct Registers {
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct Registers {
     pub a: u8, // Accumulator
     pub x: u8, // Index Register X
     pub y: u8, // Index Register X
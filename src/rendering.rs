@@ -1,6 +1,9 @@
 use crate::{WINDOW_WIDTH, WINDOW_HEIGHT};
 use crate::cart::Mirroring;
 use crate::ppu::PPU;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::Path;
 
 pub static SYSTEM_PALLETE: [(u8,u8,u8); 64] = [
     (0x80, 0x80, 0x80), (0x00, 0x3D, 0xA6), (0x00, 0x12, 0xB0), (0x44, 0x00, 0x96), (0xA1, 0x00, 0x5E),
@@ -18,13 +21,28 @@ pub static SYSTEM_PALLETE: [(u8,u8,u8); 64] = [
     (0x99, 0xFF, 0xFC), (0xDD, 0xDD, 0xDD), (0x11, 0x11, 0x11), (0x11, 0x11, 0x11)
 ];
 
+pub type Palette = [(u8, u8, u8); 64];
+
+// Reads a .pal file of RGB triples. Only the first 64 colours are used, files with emphasis variants have more
+pub fn load_palette(path: &Path) -> io::Result<Palette> {
+    let data = fs::read(path)?;
+    if data.len() < 64 * 3 || data.len() % 3 != 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} bytes is not a palette, expected 64 RGB colours", data.len())));
+    }
+    let mut palette = [(0, 0, 0); 64];
+    for (colour, rgb) in palette.iter_mut().zip(data.chunks(3)) {
+        *colour = (rgb[0], rgb[1], rgb[2]);
+    }
+    Ok(palette)
+}
+
 pub struct Frame {
     pub data: Vec<u8>,
 }
  
 impl Frame {
-    const WIDTH: usize = WINDOW_WIDTH * 2;
-    const HIGHT: usize = WINDOW_HEIGHT;
+    pub const WIDTH: usize = WINDOW_WIDTH * 2;
+    pub const HIGHT: usize = WINDOW_HEIGHT;
 
     pub fn new() -> Self {
         Frame {
@@ -32,6 +50,15 @@ impl Frame {
         }
     }
 
+    // The visible picture, without the unused right half of the buffer
+    pub fn pixels(&self) -> Vec<u8> {
+        self.data.chunks(Frame::WIDTH * 3).flat_map(|row| &row[..WINDOW_WIDTH * 3]).copied().collect()
+    }
+
+    pub fn save_png(&self, path: &Path) -> io::Result<()> {
+        write_png(path, WINDOW_WIDTH, WINDOW_HEIGHT, &self.pixels())
+    }
+
     pub fn set_pixel(&mut self, x: usize, y: usize, rgb: (u8, u8, u8)) {
         let base = y * 3 * Frame::WIDTH + x * 3;
         if base + 2 < self.data.len() {
@@ -48,6 +75,16 @@ impl Default for Frame {
     }
 }
 
+// Writes 8 bit RGB pixels
+pub fn write_png(path: &Path, width: usize, height: usize, pixels: &[u8]) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    Ok(())
+}
+
 fn bg_pallette(ppu: &PPU, attribute_table: &[u8], tile_column: usize, tile_row: usize) -> [u8; 4] {
     let attr_table_idx = tile_row / 4 * 8 + tile_column / 4;
     let attr_byte = attribute_table[attr_table_idx]; 
@@ -69,7 +106,7 @@ fn bg_pallette(ppu: &PPU, attribute_table: &[u8], tile_column: usize, tile_row:
     ]
 }
 
-pub fn render(ppu: &PPU, frame: &mut Frame) {
+pub fn render(ppu: &PPU, palette: &Palette, frame: &mut Frame) {
     let scroll_x = (ppu.scroll.scroll_x) as usize;
     let scroll_y = (ppu.scroll.scroll_y) as usize;
 
@@ -86,19 +123,19 @@ pub fn render(ppu: &PPU, frame: &mut Frame) {
         }
     };
 
-    render_name_table(ppu, frame, 
+    render_name_table(ppu, palette, frame, 
         main_nametable, 
         Rect::new(scroll_x, scroll_y, WINDOW_WIDTH, WINDOW_HEIGHT ),
         -(scroll_x as isize), -(scroll_y as isize)
     );
     if scroll_x > 0 {
-        render_name_table(ppu, frame, 
+        render_name_table(ppu, palette, frame, 
             second_nametable, 
             Rect::new(0, 0, scroll_x, WINDOW_HEIGHT),
             (WINDOW_WIDTH - scroll_x) as isize, 0
         );
     } else if scroll_y > 0 {
-        render_name_table(ppu, frame, 
+        render_name_table(ppu, palette, frame, 
             second_nametable, 
             Rect::new(0, 0, WINDOW_WIDTH, scroll_y),
             0, (WINDOW_HEIGHT - scroll_y) as isize
@@ -130,9 +167,9 @@ pub fn render(ppu: &PPU, frame: &mut Frame) {
                 lower >>= 1;
                 let rgb = match value {
                     0 => continue 'ololo, // skip coloring the pixel
-                    1 => SYSTEM_PALLETE[sprite_palette[1] as usize],
-                    2 => SYSTEM_PALLETE[sprite_palette[2] as usize],
-                    3 => SYSTEM_PALLETE[sprite_palette[3] as usize],
+                    1 => palette[sprite_palette[1] as usize],
+                    2 => palette[sprite_palette[2] as usize],
+                    3 => palette[sprite_palette[3] as usize],
                     _ => panic!("can't be"),
                 };
                 match (flip_horizontal, flip_vertical) {
@@ -183,7 +220,7 @@ impl Rect {
 }
 
 #[allow(clippy::needless_range_loop)]
-fn render_name_table(ppu: &PPU, frame: &mut Frame, name_table: &[u8], view_port: Rect, shift_x: isize, shift_y: isize) {
+fn render_name_table(ppu: &PPU, system_palette: &Palette, frame: &mut Frame, name_table: &[u8], view_port: Rect, shift_x: isize, shift_y: isize) {
     let bank = ppu.bknd_pattern_addr();
     
     let attribute_table = &name_table[0x3c0.. 0x400];
@@ -204,10 +241,10 @@ fn render_name_table(ppu: &PPU, frame: &mut Frame, name_table: &[u8], view_port:
                 upper >>= 1;
                 lower >>= 1;
                 let rgb = match value {
-                    0 => SYSTEM_PALLETE[ppu.palette_table[0] as usize],
-                    1 => SYSTEM_PALLETE[palette[1] as usize],
-                    2 => SYSTEM_PALLETE[palette[2] as usize],
-                    3 => SYSTEM_PALLETE[palette[3] as usize],
+                    0 => system_palette[ppu.palette_table[0] as usize],
+                    1 => system_palette[palette[1] as usize],
+                    2 => system_palette[palette[2] as usize],
+                    3 => system_palette[palette[3] as usize],
                     _ => panic!("can't be"),
                 };
                 let pixel_x = tile_column * 8 + x;
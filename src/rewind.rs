@@ -0,0 +1,206 @@
+use std::collections::VecDeque;
+
+use crate::nes::Nes;
+
+/*
+    Rewind buffer. A snapshot is taken every `interval` frames, the newest is kept whole and every older one is
+    stored as a delta against the snapshot taken after it. Stepping back undoes one delta at a time, and the oldest
+    deltas are dropped once the buffer goes over its memory budget.
+
+    A delta is the length of the older snapshot followed by runs of
+        skip: u32, count: u32, bytes: [u8; count]
+    each run skipping over bytes both snapshots share and then replacing count bytes.
+*/
+pub struct Rewind {
+    interval: usize,
+    budget: usize,
+    head: Vec<u8>,
+    deltas: VecDeque<Vec<u8>>,
+    used: usize,
+    scratch: Vec<u8>,
+    frames: usize,
+    held: usize,
+}
+
+impl Rewind {
+    pub fn new(interval: usize, budget: usize) -> Self {
+        assert!(interval > 0, "Rewind interval must be at least one frame");
+        Rewind {
+            interval,
+            budget,
+            head: Vec::new(),
+            deltas: VecDeque::new(),
+            used: 0,
+            scratch: Vec::new(),
+            frames: 0,
+            held: 0,
+        }
+    }
+
+    // Bytes currently used by the stored snapshots
+    pub fn used(&self) -> usize {
+        self.head.len() + self.used
+    }
+
+    // Number of snapshots that can be stepped back through
+    pub fn len(&self) -> usize {
+        if self.head.is_empty() { 0 } else { self.deltas.len() + 1 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.head.clear();
+        self.deltas.clear();
+        self.used = 0;
+        self.frames = 0;
+        self.held = 0;
+    }
+
+    // Call once per frame of normal play
+    pub fn push(&mut self, nes: &Nes) {
+        self.held = 0;
+        self.frames += 1;
+        if !self.frames.is_multiple_of(self.interval) {
+            return;
+        }
+
+        nes.snapshot_into(&mut self.scratch);
+        if !self.head.is_empty() {
+            let delta = encode_delta(&self.head, &self.scratch);
+            self.used += delta.len();
+            self.deltas.push_back(delta);
+        }
+        std::mem::swap(&mut self.head, &mut self.scratch);
+
+        while self.used() > self.budget {
+            match self.deltas.pop_front() {
+                Some(delta) => self.used -= delta.len(),
+                None => break,
+            }
+        }
+    }
+
+    /*
+        Call once per frame while rewinding, in place of running the game. Steps back one snapshot every `interval`
+        calls so the game runs backwards at normal speed, and redraws the frame the snapshot was taken on.
+        Returns false once there is nothing older to go back to, the oldest frame is kept on screen.
+    */
+    pub fn rewind(&mut self, nes: &mut Nes) -> bool {
+        if self.head.is_empty() {
+            return false;
+        }
+
+        let mut moved = true;
+        if self.held > 0 && self.held.is_multiple_of(self.interval) {
+            match self.deltas.pop_back() {
+                Some(delta) => {
+                    self.used -= delta.len();
+                    apply_delta(&mut self.head, &delta);
+                }
+                None => moved = false,
+            }
+        }
+        self.held += 1;
+        self.frames = 0;
+
+        nes.restore(&self.head).expect("Rewind snapshot could not be restored");
+        nes.render();
+        moved
+    }
+}
+
+// Describes how to turn newer back into older
+fn encode_delta(older: &[u8], newer: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    delta.extend_from_slice(&(older.len() as u32).to_le_bytes());
+
+    let differs = |i: usize| newer.get(i) != Some(&older[i]);
+    let mut i = 0;
+    while i < older.len() {
+        let start = i;
+        while i < older.len() && !differs(i) {
+            i += 1;
+        }
+        if i == older.len() {
+            break;
+        }
+        let skip = i - start;
+
+        let run_start = i;
+        while i < older.len() && differs(i) {
+            i += 1;
+        }
+        delta.extend_from_slice(&(skip as u32).to_le_bytes());
+        delta.extend_from_slice(&((i - run_start) as u32).to_le_bytes());
+        delta.extend_from_slice(&older[run_start..i]);
+    }
+    delta
+}
+
+fn apply_delta(data: &mut Vec<u8>, delta: &[u8]) {
+    let read_u32 = |at: usize| u32::from_le_bytes(delta[at..at + 4].try_into().unwrap()) as usize;
+
+    data.resize(read_u32(0), 0);
+    let mut pos = 4;
+    let mut offset = 0;
+    while pos < delta.len() {
+        offset += read_u32(pos);
+        let count = read_u32(pos + 4);
+        pos += 8;
+        data[offset..offset + count].copy_from_slice(&delta[pos..pos + count]);
+        offset += count;
+        pos += count;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::nestest;
+
+    #[test]
+    fn test_delta() {
+        let older = vec![1, 2, 3, 4, 5, 6];
+        for newer in [vec![1, 9, 3, 4, 9, 6], vec![1, 2, 3], vec![9, 2, 3, 4, 5, 6, 7, 8], vec![1, 2, 3, 4, 5, 6]] {
+            let mut data = newer.clone();
+            apply_delta(&mut data, &encode_delta(&older, &newer));
+            assert_eq!(data, older);
+        }
+    }
+
+    #[test]
+    fn test_rewind() {
+        let mut nes = nestest();
+        let mut rewind = Rewind::new(1, usize::MAX);
+        let mut history = Vec::new();
+        for _ in 0..5 {
+            nes.run_frame();
+            history.push((nes.snapshot(), nes.framebuffer().data.clone()));
+            rewind.push(&nes);
+        }
+        assert_eq!(rewind.len(), 5);
+
+        for (snapshot, frame) in history.iter().rev() {
+            assert!(rewind.rewind(&mut nes));
+            assert_eq!(&nes.snapshot(), snapshot);
+            assert_eq!(&nes.framebuffer().data, frame);
+        }
+        assert!(!rewind.rewind(&mut nes));
+        assert_eq!(nes.snapshot(), history[0].0);
+    }
+
+    #[test]
+    fn test_budget() {
+        let mut nes = nestest();
+        let mut rewind = Rewind::new(1, 0);
+        for _ in 0..5 {
+            nes.run_frame();
+            rewind.push(&nes);
+        }
+        // The newest snapshot is always kept
+        assert_eq!(rewind.len(), 1);
+    }
+}
@@ -0,0 +1,246 @@
+/*
+    Runs the community single-step 6502 test vectors (https://github.com/SingleStepTests/65x02, nes6502 set)
+    against the CPU on a flat 64 KiB memory rather than the NES bus.
+
+    Each opcode has a file of cases holding the initial and final registers/RAM and the bus activity
+    for every cycle the instruction took. A case passes when the final state and the number of cycles
+    match. The CPU doesn't make the dummy reads and writes the 6502 does yet, so the bus activity is a
+    known gap: the first cycle that differs is reported for each opcode, but doesn't fail the test.
+    The vectors are not distributed with the repo, drop the xx.json files into roms/tests/nes6502 and
+    run with --ignored.
+*/
+use std::fs::File;
+use std::io::BufReader;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+use crate::bus::Memory;
+use crate::cpu::CPU;
+
+#[derive(Deserialize)]
+struct CpuState {
+    pc: u16,
+    s: u8,
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    ram: Vec<(u16, u8)>,
+}
+
+#[derive(Deserialize)]
+struct TestCase {
+    name: String,
+    initial: CpuState,
+    #[serde(rename = "final")]
+    expected: CpuState,
+    cycles: Vec<BusAccess>,
+}
+
+// Bus activity in the form the vectors use: address, value, and "read" or "write"
+type BusAccess = (u16, u8, String);
+
+struct FlatMemory {
+    data: Vec<u8>,
+    cycles: usize,
+    accesses: Vec<BusAccess>,
+}
+
+impl FlatMemory {
+    fn new() -> Self {
+        FlatMemory {
+            data: vec![0; 0x10000],
+            cycles: 0,
+            accesses: Vec::new(),
+        }
+    }
+}
+
+impl Memory for FlatMemory {
+    fn read(&mut self, addr: u16) -> u8 {
+        let value = self.data[addr as usize];
+        self.accesses.push((addr, value, "read".to_string()));
+        value
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.data[addr as usize] = value;
+        self.accesses.push((addr, value, "write".to_string()));
+    }
+
+    fn tick(&mut self, cycles: u8) {
+        self.cycles += cycles as usize;
+    }
+}
+
+fn setup(state: &CpuState) -> CPU<FlatMemory> {
+    let mut cpu = CPU::new(FlatMemory::new());
+    cpu.regs.pc = state.pc;
+    cpu.regs.sp = state.s;
+    cpu.regs.a = state.a;
+    cpu.regs.x = state.x;
+    cpu.regs.y = state.y;
+    cpu.regs.p = state.p;
+    for &(addr, value) in &state.ram {
+        cpu.bus.data[addr as usize] = value;
+    }
+    cpu
+}
+
+struct CaseResult {
+    // Mismatches in the final state and cycle count
+    errors: Vec<String>,
+    // First cycle whose bus activity differs
+    bus_mismatch: Option<String>,
+}
+
+// Returns a description of every mismatch between the CPU and the expected final state
+fn compare(cpu: &mut CPU<FlatMemory>, test: &TestCase) -> Vec<String> {
+    let expected = &test.expected;
+    let mut errors = Vec::new();
+
+    let registers = [
+        ("PC", cpu.regs.pc, expected.pc),
+        ("S", cpu.regs.sp as u16, expected.s as u16),
+        ("A", cpu.regs.a as u16, expected.a as u16),
+        ("X", cpu.regs.x as u16, expected.x as u16),
+        ("Y", cpu.regs.y as u16, expected.y as u16),
+        ("P", cpu.regs.p as u16, expected.p as u16),
+    ];
+    for (name, actual, wanted) in registers {
+        if actual != wanted {
+            errors.push(format!("{} is {:04X}, expected {:04X}", name, actual, wanted));
+        }
+    }
+
+    for &(addr, wanted) in &expected.ram {
+        let actual = cpu.bus.data[addr as usize];
+        if actual != wanted {
+            errors.push(format!("${:04X} is {:02X}, expected {:02X}", addr, actual, wanted));
+        }
+    }
+
+    if cpu.bus.cycles != test.cycles.len() {
+        errors.push(format!("took {} cycles, expected {}", cpu.bus.cycles, test.cycles.len()));
+    }
+
+    errors
+}
+
+fn compare_bus(cpu: &CPU<FlatMemory>, test: &TestCase) -> Option<String> {
+    let accesses = &cpu.bus.accesses;
+    let describe = |access: Option<&BusAccess>| match access {
+        Some((addr, value, kind)) => format!("{} {:02X} at ${:04X}", kind, value, addr),
+        None => "nothing".to_string(),
+    };
+    (0..accesses.len().max(test.cycles.len()))
+        .find(|&cycle| accesses.get(cycle) != test.cycles.get(cycle))
+        .map(|cycle| format!("cycle {} did {}, expected {}", cycle, describe(accesses.get(cycle)), describe(test.cycles.get(cycle))))
+}
+
+fn run_case(test: &TestCase) -> CaseResult {
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut cpu = setup(&test.initial);
+        cpu.step();
+        CaseResult {
+            errors: compare(&mut cpu, test),
+            bus_mismatch: compare_bus(&cpu, test),
+        }
+    }));
+    outcome.unwrap_or_else(|_| CaseResult { errors: vec!["panicked".to_string()], bus_mismatch: None })
+}
+
+// Runs every case in a vector file, returning the names and results of the cases that didn't fully match
+fn run_file(path: &Path) -> Vec<(String, CaseResult)> {
+    let file = File::open(path).unwrap_or_else(|_| panic!("Unable to open file {}", path.display()));
+    let tests: Vec<TestCase> = serde_json::from_reader(BufReader::new(file))
+        .unwrap_or_else(|err| panic!("Unable to parse {}: {}", path.display(), err));
+
+    tests.iter()
+        .map(|test| (test.name.clone(), run_case(test)))
+        .filter(|(_, result)| !result.errors.is_empty() || result.bus_mismatch.is_some())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A few cases in the vectors' format, so the harness itself runs without them
+    #[test]
+    fn test_single_step_cases() {
+        let tests: Vec<TestCase> = serde_json::from_str(r#"[
+            {
+                "name": "a9 lda immediate",
+                "initial": { "pc": 512, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [[512, 169], [513, 128]] },
+                "final": { "pc": 514, "s": 253, "a": 128, "x": 0, "y": 0, "p": 164, "ram": [[512, 169], [513, 128]] },
+                "cycles": [[512, 169, "read"], [513, 128, "read"]]
+            },
+            {
+                "name": "8d sta absolute",
+                "initial": { "pc": 512, "s": 253, "a": 66, "x": 0, "y": 0, "p": 36, "ram": [[512, 141], [513, 52], [514, 18]] },
+                "final": { "pc": 515, "s": 253, "a": 66, "x": 0, "y": 0, "p": 36, "ram": [[4660, 66]] },
+                "cycles": [[512, 141, "read"], [513, 52, "read"], [514, 18, "read"], [4660, 66, "write"]]
+            },
+            {
+                "name": "00 brk",
+                "initial": { "pc": 512, "s": 253, "a": 0, "x": 0, "y": 0, "p": 32, "ram": [[512, 0], [513, 7], [65534, 0], [65535, 128]] },
+                "final": { "pc": 32768, "s": 250, "a": 0, "x": 0, "y": 0, "p": 36, "ram": [[509, 2], [508, 2], [507, 48]] },
+                "cycles": [[512, 0, "read"], [513, 7, "read"], [509, 2, "write"], [508, 2, "write"], [507, 48, "write"], [65534, 0, "read"], [65535, 128, "read"]]
+            }
+        ]"#).unwrap();
+
+        for test in &tests {
+            let result = run_case(test);
+            assert_eq!(result.errors, Vec::<String>::new(), "{}", test.name);
+            assert_eq!(result.bus_mismatch, None, "{}", test.name);
+        }
+
+        // A missing dummy read is reported on its own, the result and cycle count are right
+        let mut test: TestCase = serde_json::from_str(r#"{
+            "name": "e8 inx",
+            "initial": { "pc": 512, "s": 253, "a": 0, "x": 1, "y": 0, "p": 36, "ram": [[512, 232], [513, 0]] },
+            "final": { "pc": 513, "s": 253, "a": 0, "x": 2, "y": 0, "p": 36, "ram": [] },
+            "cycles": [[512, 232, "read"], [513, 0, "read"]]
+        }"#).unwrap();
+        let result = run_case(&test);
+        assert!(result.errors.is_empty());
+        assert_eq!(result.bus_mismatch.as_deref(), Some("cycle 1 did nothing, expected read 00 at $0201"));
+        test.cycles.pop();
+        assert_eq!(run_case(&test).errors, ["took 2 cycles, expected 1"]);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_single_step_vectors() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("roms/tests/nes6502");
+        assert!(dir.is_dir(), "Test vectors not found in {}", dir.display());
+
+        // Out of range reads/writes are reported as failures, keep their panic messages out of the output
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(|_| {}));
+
+        let mut failed_opcodes = Vec::new();
+        for opcode in 0..=0xFFu8 {
+            let path = dir.join(format!("{:02x}.json", opcode));
+            if !path.exists() {
+                continue;
+            }
+
+            let results = run_file(&path);
+            let failures: Vec<_> = results.iter().filter(|(_, result)| !result.errors.is_empty()).collect();
+            if let Some((name, result)) = failures.first() {
+                println!("{:02X}: {} cases failed, first \"{}\": {}", opcode, failures.len(), name, result.errors.join(", "));
+                failed_opcodes.push(opcode);
+            }
+            // Known gap, reported without failing
+            let bus_mismatches: Vec<_> = results.iter().filter_map(|(name, result)| Some((name, result.bus_mismatch.as_ref()?))).collect();
+            if let Some((name, mismatch)) = bus_mismatches.first() {
+                println!("{:02X}: {} cases differ on the bus, first \"{}\": {}", opcode, bus_mismatches.len(), name, mismatch);
+            }
+        }
+
+        panic::set_hook(hook);
+        assert!(failed_opcodes.is_empty(), "Failed opcodes: {:02X?}", failed_opcodes);
+    }
+}
@@ -0,0 +1,154 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::nes::Nes;
+use crate::rendering::{Frame, write_png};
+use crate::state::{State, StateError};
+use crate::{WINDOW_HEIGHT, WINDOW_WIDTH};
+
+pub const SLOT_COUNT: u8 = 10;
+
+const THUMBNAIL_WIDTH: usize = WINDOW_WIDTH / 2;
+const THUMBNAIL_HEIGHT: usize = WINDOW_HEIGHT / 2;
+
+#[derive(Deserialize, Serialize)]
+struct SlotMetadata {
+    // Seconds since the Unix epoch
+    saved_at: u64,
+}
+
+pub struct SlotInfo {
+    pub slot: u8,
+    pub saved_at: SystemTime,
+    pub thumbnail: PathBuf,
+}
+
+/*
+    Numbered save state slots for one ROM. Each slot is three files in a directory named after the ROM hash:
+        <slot>.state    the machine state
+        <slot>.png      half size thumbnail of the frame at the time of saving
+        <slot>.toml     when the state was saved
+*/
+pub struct SaveSlots {
+    dir: PathBuf,
+    rom_hash: u32,
+}
+
+impl SaveSlots {
+    pub fn new<P: AsRef<Path>>(root: P, rom_hash: u32) -> Self {
+        SaveSlots {
+            dir: root.as_ref().join(format!("{:08x}", rom_hash)),
+            rom_hash,
+        }
+    }
+
+    // The platform data directory, or save-states in the working directory when there isn't one
+    pub fn default_root() -> PathBuf {
+        match dirs::data_dir() {
+            Some(dir) => dir.join("nes-emulator-rs").join("save-states"),
+            None => PathBuf::from("save-states"),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, slot: u8, extension: &str) -> Result<PathBuf, StateError> {
+        if slot >= SLOT_COUNT {
+            return Err(StateError::NoSuchSlot(slot));
+        }
+        Ok(self.dir.join(format!("{}.{}", slot, extension)))
+    }
+
+    pub fn save(&self, slot: u8, nes: &Nes) -> Result<(), StateError> {
+        let path = self.path(slot, "state")?;
+        fs::create_dir_all(&self.dir)?;
+        fs::write(path, nes.state().to_bytes(self.rom_hash))?;
+        write_thumbnail(&self.path(slot, "png")?, nes.framebuffer())?;
+
+        let saved_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let metadata = toml::to_string(&SlotMetadata { saved_at }).unwrap();
+        fs::write(self.path(slot, "toml")?, metadata)?;
+        Ok(())
+    }
+
+    pub fn load(&self, slot: u8, nes: &mut Nes) -> Result<(), StateError> {
+        let data = fs::read(self.path(slot, "state")?)?;
+        nes.set_state(State::from_bytes(&data, self.rom_hash)?);
+        Ok(())
+    }
+
+    // Every slot holding a state, in slot order
+    pub fn list(&self) -> Vec<SlotInfo> {
+        (0..SLOT_COUNT).filter_map(|slot| self.info(slot)).collect()
+    }
+
+    // None for empty slots and slots out of range
+    pub fn info(&self, slot: u8) -> Option<SlotInfo> {
+        if !self.path(slot, "state").ok()?.exists() {
+            return None;
+        }
+        let metadata = fs::read_to_string(self.path(slot, "toml").ok()?).ok()?;
+        let metadata: SlotMetadata = toml::from_str(&metadata).ok()?;
+
+        Some(SlotInfo {
+            slot,
+            saved_at: UNIX_EPOCH + Duration::from_secs(metadata.saved_at),
+            thumbnail: self.path(slot, "png").ok()?,
+        })
+    }
+}
+
+// Averages each 2x2 block of pixels
+fn write_thumbnail(path: &Path, frame: &Frame) -> io::Result<()> {
+    let mut pixels = Vec::with_capacity(THUMBNAIL_WIDTH * THUMBNAIL_HEIGHT * 3);
+    for y in 0..THUMBNAIL_HEIGHT {
+        for x in 0..THUMBNAIL_WIDTH {
+            for channel in 0..3 {
+                let mut sum = 0;
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    sum += frame.data[((y * 2 + dy) * Frame::WIDTH + x * 2 + dx) * 3 + channel] as u32;
+                }
+                pixels.push((sum / 4) as u8);
+            }
+        }
+    }
+
+    write_png(path, THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT, &pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::nestest;
+
+    #[test]
+    fn test_save_and_list_slots() {
+        let root = std::env::temp_dir().join(format!("nes-slots-{}", std::process::id()));
+        let mut nes = nestest();
+        nes.run_frame();
+
+        let slots = SaveSlots::new(&root, nes.rom_hash());
+        assert!(slots.list().is_empty());
+        slots.save(3, &nes).unwrap();
+        slots.save(7, &nes).unwrap();
+
+        let listed: Vec<u8> = slots.list().iter().map(|info| info.slot).collect();
+        assert_eq!(listed, [3, 7]);
+        assert!(slots.info(3).unwrap().thumbnail.exists());
+
+        let ram = *nes.ram();
+        nes.run_frame();
+        slots.load(3, &mut nes).unwrap();
+        assert_eq!(*nes.ram(), ram);
+        assert!(matches!(slots.load(4, &mut nes), Err(StateError::Io(_))));
+        assert!(matches!(slots.load(SLOT_COUNT, &mut nes), Err(StateError::NoSuchSlot(_))));
+        assert!(matches!(slots.save(SLOT_COUNT, &nes), Err(StateError::NoSuchSlot(_))));
+        assert!(slots.info(SLOT_COUNT).is_none());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -1,33 +1,168 @@
-use std::{fs::File, io::{Write, BufReader, Result}};
+use std::{fmt, io};
 
-use serde_big_array::BigArray;
+use serde::Deserialize;
 
+use crate::devices::Device;
 use crate::ppu::PPU;
+use crate::registers::Registers;
 
+const MAGIC: [u8; 4] = *b"NESS";
+// Bump whenever the layout of State changes, older states are rejected rather than misread
+const FORMAT_VERSION: u16 = 6;
+
+#[derive(Debug)]
+pub enum StateError {
+    Io(io::Error),
+    NotAState,
+    UnsupportedVersion(u16),
+    WrongRom { expected: u32, found: u32 },
+    Corrupt(String),
+    // Save slot number past the last slot
+    NoSuchSlot(u8),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::Io(err) => write!(f, "{}", err),
+            StateError::NotAState => write!(f, "Data is not a save state"),
+            StateError::UnsupportedVersion(version) => write!(f, "Save state version {} is not supported, expected {}", version, FORMAT_VERSION),
+            StateError::WrongRom { expected, found } => write!(f, "Save state is for ROM {:08X}, loaded ROM is {:08X}", found, expected),
+            StateError::Corrupt(err) => write!(f, "Save state is corrupt: {}", err),
+            StateError::NoSuchSlot(slot) => write!(f, "There is no save slot {}", slot),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+impl From<io::Error> for StateError {
+    fn from(err: io::Error) -> Self {
+        StateError::Io(err)
+    }
+}
+
+// Written in front of every state so it can be checked before the rest is decoded
 #[derive(Deserialize, Serialize)]
+struct Header {
+    magic: [u8; 4],
+    version: u16,
+    rom_hash: u32,
+}
+
+// Snapshot of the whole machine, everything needed to carry on exactly where it left off
+#[derive(Clone, Deserialize, Serialize)]
 pub struct State {
-    #[serde(with = "BigArray")]
+    pub regs: Registers,
+    #[serde(with = "serde_bytes")]
     pub ram: [u8; 2048],
+    #[serde(with = "serde_bytes")]
+    pub prg_ram: [u8; 0x2000],
+    pub cycles: usize,
     pub ppu: PPU,
+    pub ports: [Device; 2],
+}
+
+// Borrows the machine rather than copying it, serializes exactly the same as State
+#[derive(Serialize)]
+pub struct StateRef<'a> {
+    pub regs: &'a Registers,
+    #[serde(with = "serde_bytes")]
+    pub ram: &'a [u8; 2048],
+    #[serde(with = "serde_bytes")]
+    pub prg_ram: &'a [u8; 0x2000],
+    pub cycles: usize,
+    pub ppu: &'a PPU,
+    pub ports: &'a [Device; 2],
+}
+
+impl StateRef<'_> {
+    // Overwrites buf, keeping its allocation
+    pub fn write_to(&self, rom_hash: u32, buf: &mut Vec<u8>) {
+        buf.clear();
+        let header = Header {
+            magic: MAGIC,
+            version: FORMAT_VERSION,
+            rom_hash,
+        };
+        rmp_serde::encode::write(buf, &header).unwrap();
+        rmp_serde::encode::write(buf, self).unwrap();
+    }
 }
 
 impl State {
-    pub fn new(ram: [u8; 2048], ppu: PPU) -> Self {
-        Self {
-            ram,
-            ppu
+    pub fn by_ref(&self) -> StateRef<'_> {
+        StateRef {
+            regs: &self.regs,
+            ram: &self.ram,
+            prg_ram: &self.prg_ram,
+            cycles: self.cycles,
+            ppu: &self.ppu,
+            ports: &self.ports,
         }
     }
 
-    pub fn save_state(self, state_number: u8) {
-        let mut file = File::create(format!("save-state-{}", state_number)).expect("Failed to save state to file");
-        let _ = file.write_all(&rmp_serde::to_vec(&self).unwrap());
+    pub fn to_bytes(&self, rom_hash: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        self.by_ref().write_to(rom_hash, &mut data);
+        data
     }
-    
-    pub fn load_state(state_number: u8) -> Result<Self> {
-        let file = File::open(format!("save-state-{}", state_number))?;
-        let reader = BufReader::new(file);
-        let s: State = rmp_serde::from_read(reader).unwrap();
-        Ok(s)
+
+    // Fails unless the data is a state of the current version taken while running the ROM with the given hash
+    pub fn from_bytes(data: &[u8], rom_hash: u32) -> Result<Self, StateError> {
+        let mut de = rmp_serde::Deserializer::new(data);
+
+        let header = Header::deserialize(&mut de).map_err(|_| StateError::NotAState)?;
+        if header.magic != MAGIC {
+            return Err(StateError::NotAState);
+        }
+        if header.version != FORMAT_VERSION {
+            return Err(StateError::UnsupportedVersion(header.version));
+        }
+        if header.rom_hash != rom_hash {
+            return Err(StateError::WrongRom { expected: rom_hash, found: header.rom_hash });
+        }
+
+        State::deserialize(&mut de).map_err(|err| StateError::Corrupt(err.to_string()))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cart::Mirroring;
+    use crate::devices::DeviceType;
+
+    fn state() -> State {
+        State {
+            regs: Registers { a: 1, x: 2, y: 3, p: 4, sp: 5, pc: 0x8006 },
+            ram: [7; 2048],
+            prg_ram: [8; 0x2000],
+            cycles: 9,
+            ppu: PPU::new(vec![0; 0x2000], Mirroring::Horizontal),
+            ports: [Device::new(DeviceType::Controller, 0), Device::new(DeviceType::Zapper, 1)],
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let loaded = State::from_bytes(&state().to_bytes(0x1234), 0x1234).unwrap();
+        assert_eq!(loaded.regs.pc, 0x8006);
+        assert_eq!(loaded.prg_ram, [8; 0x2000]);
+        assert_eq!(loaded.cycles, 9);
+        // CHR ROM is taken from the cartridge rather than stored
+        assert!(loaded.ppu.chr_rom.is_empty());
+    }
+
+    #[test]
+    fn test_rejects_mismatched_states() {
+        let data = state().to_bytes(0x1234);
+        assert!(matches!(State::from_bytes(&data, 0x4321), Err(StateError::WrongRom { expected: 0x4321, found: 0x1234 })));
+        assert!(matches!(State::from_bytes(b"not a state", 0x1234), Err(StateError::NotAState)));
+        assert!(matches!(State::from_bytes(&data[..data.len() / 2], 0x1234), Err(StateError::Corrupt(_))));
+
+        let mut old = rmp_serde::to_vec(&Header { magic: MAGIC, version: 0, rom_hash: 0x1234 }).unwrap();
+        old.extend(rmp_serde::to_vec(&state()).unwrap());
+        assert!(matches!(State::from_bytes(&old, 0x1234), Err(StateError::UnsupportedVersion(0))));
+    }
+}
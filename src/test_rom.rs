@@ -0,0 +1,208 @@
+/*
+    Runner for test ROMs using the $6000 result protocol (blargg's cpu_instrs, instr_timing, ppu_vbl_nmi, ...)
+    https://github.com/christopherpow/nes-test-roms/blob/master/readme.txt
+
+    $6000      status: $80 while running, $81 when the ROM wants a reset, otherwise the result code
+    $6001-6003 signature $DE $B0 $61, written once the protocol is in use
+    $6004-     NUL-terminated text output
+*/
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use crate::bus::{Bus, Memory};
+use crate::cart::{Cart, CartError};
+use crate::cpu::CPU;
+
+const STATUS_ADDR: u16 = 0x6000;
+const SIGNATURE_ADDR: u16 = 0x6001;
+const TEXT_ADDR: u16 = 0x6004;
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+
+const STATUS_RUNNING: u8 = 0x80;
+const STATUS_RESET: u8 = 0x81;
+
+// Roughly one minute of emulated time at the NTSC CPU clock
+const CYCLE_LIMIT: usize = 60 * 1_789_773;
+// The ROM asks for a reset no sooner than 100ms after writing $81
+const RESET_DELAY: usize = 1_789_773 / 10;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TestRomStatus {
+    Passed,
+    Failed(u8),
+    NoSignature,
+    TimedOut,
+    Crashed(String),
+    // The ROM needs a mapper the emulator doesn't have yet
+    Unsupported(String),
+}
+
+#[derive(Debug)]
+pub struct TestRomResult {
+    pub path: PathBuf,
+    pub status: TestRomStatus,
+    pub text: String,
+}
+
+impl TestRomResult {
+    pub fn passed(&self) -> bool {
+        self.status == TestRomStatus::Passed
+    }
+}
+
+fn has_signature(cpu: &mut CPU<Bus>) -> bool {
+    (0..3).all(|i| cpu.read(SIGNATURE_ADDR + i) == SIGNATURE[i as usize])
+}
+
+fn read_text(cpu: &mut CPU<Bus>) -> String {
+    let mut text = Vec::new();
+    for addr in TEXT_ADDR..=0x7FFF {
+        let c = cpu.read(addr);
+        if c == 0 {
+            break;
+        }
+        text.push(c);
+    }
+    String::from_utf8_lossy(&text).trim().to_string()
+}
+
+fn execute(cpu: &mut CPU<Bus>) -> (TestRomStatus, String) {
+    let mut reset_at = None;
+
+    while cpu.bus.cycles() < CYCLE_LIMIT {
+        if let Some(_nmi) = cpu.bus.poll_nmi_status() {
+            cpu.interrupt_nmi();
+        }
+
+        // BRK is executed like any other instruction, the cycle limit catches ROMs that never finish
+        cpu.step();
+
+        if !has_signature(cpu) {
+            continue;
+        }
+
+        match cpu.read(STATUS_ADDR) {
+            STATUS_RUNNING => {},
+            STATUS_RESET => {
+                let due = *reset_at.get_or_insert(cpu.bus.cycles() + RESET_DELAY);
+                if cpu.bus.cycles() >= due {
+                    reset_at = None;
+                    cpu.reset();
+                }
+            }
+            0 => return (TestRomStatus::Passed, read_text(cpu)),
+            code => return (TestRomStatus::Failed(code), read_text(cpu)),
+        }
+    }
+
+    if has_signature(cpu) {
+        (TestRomStatus::TimedOut, read_text(cpu))
+    } else {
+        (TestRomStatus::NoSignature, String::new())
+    }
+}
+
+pub fn run_test_rom(path: &Path) -> TestRomResult {
+    let cart = match Cart::load(&path.to_string_lossy()) {
+        Ok(cart) => cart,
+        Err(err) => {
+            let status = match err {
+                CartError::UnsupportedMapper(_) => TestRomStatus::Unsupported(err.to_string()),
+                _ => TestRomStatus::Crashed(err.to_string()),
+            };
+            return TestRomResult { path: path.to_path_buf(), status, text: String::new() };
+        }
+    };
+
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        let bus = Bus::new(cart);
+        let mut cpu = CPU::new(bus);
+        cpu.reset();
+        execute(&mut cpu)
+    }));
+
+    let (status, text) = outcome.unwrap_or_else(|err| {
+        let message = err.downcast_ref::<String>().cloned()
+            .or_else(|| err.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_default();
+        (TestRomStatus::Crashed(message), String::new())
+    });
+
+    TestRomResult {
+        path: path.to_path_buf(),
+        status,
+        text,
+    }
+}
+
+// Runs every .nes file below the directory, in path order
+pub fn run_test_rom_dir(dir: &Path) -> Vec<TestRomResult> {
+    let mut roms = Vec::new();
+    collect_roms(dir, &mut roms);
+    roms.sort();
+    roms.iter().map(|path| run_test_rom(path)).collect()
+}
+
+fn collect_roms(dir: &Path, roms: &mut Vec<PathBuf>) {
+    let entries = fs::read_dir(dir).unwrap_or_else(|_| panic!("Unable to read directory {}", dir.display()));
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_roms(&path, roms);
+        } else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("nes")) {
+            roms.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The suites are not distributed with the repo, drop them into roms/tests/<suite> and run with --ignored
+    fn run_suite(suite: &str) {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("roms/tests").join(suite);
+        let results = run_test_rom_dir(&dir);
+        assert!(!results.is_empty(), "No test ROMs found in {}", dir.display());
+
+        for result in &results {
+            println!("{} {:?}\n{}", result.path.display(), result.status, result.text);
+        }
+        // ROMs on mappers that aren't implemented yet are reported above, but don't fail the suite
+        let supported: Vec<_> = results.iter().filter(|r| !matches!(r.status, TestRomStatus::Unsupported(_))).collect();
+        assert!(!supported.is_empty(), "No test ROMs in {} use a supported mapper", dir.display());
+        let failed: Vec<_> = supported.iter().filter(|r| !r.passed()).map(|r| r.path.display().to_string()).collect();
+        assert!(failed.is_empty(), "Failed test ROMs: {:?}", failed);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_cpu_instrs() {
+        run_suite("cpu_instrs");
+    }
+
+    #[test]
+    #[ignore]
+    fn test_instr_timing() {
+        run_suite("instr_timing");
+    }
+
+    #[test]
+    #[ignore]
+    fn test_ppu_vbl_nmi() {
+        run_suite("ppu_vbl_nmi");
+    }
+
+    #[test]
+    #[ignore]
+    fn test_sprite_hit() {
+        run_suite("sprite_hit");
+    }
+
+    #[test]
+    #[ignore]
+    fn test_apu() {
+        run_suite("apu_test");
+    }
+}
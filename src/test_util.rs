@@ -0,0 +1,13 @@
+// Fixtures shared by the unit tests
+use crate::cart::Cart;
+use crate::nes::Nes;
+
+pub const NESTEST_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/roms/tests/nestest.nes");
+
+pub fn nestest_cart() -> Cart {
+    Cart::new(NESTEST_PATH)
+}
+
+pub fn nestest() -> Nes {
+    Nes::new(nestest_cart())
+}
@@ -0,0 +1,79 @@
+use crate::joypad::Inputs;
+use crate::nes::{Nes, PLAYERS};
+
+/*
+    Turbo A and B. While a turbo button is held the real button is pressed and released by itself,
+    switching every `frames` frames, so it is pressed 60 / (2 * frames) times a second.
+*/
+pub struct Turbo {
+    frames: usize,
+    held: [[bool; 2]; PLAYERS],
+    frame: usize,
+}
+
+impl Turbo {
+    pub const BUTTONS: [Inputs; 2] = [Inputs::A, Inputs::B];
+
+    pub fn new(frames: usize) -> Self {
+        assert!(frames > 0, "Turbo needs at least one frame per press");
+        Turbo {
+            frames,
+            held: [[false; 2]; PLAYERS],
+            frame: 0,
+        }
+    }
+
+    // Presses per second at 60 frames a second, rounded to the nearest rate turbo can manage
+    pub fn with_rate(presses_per_second: usize) -> Self {
+        Turbo::new((30 / presses_per_second.max(1)).max(1))
+    }
+
+    pub fn set_held(&mut self, nes: &mut Nes, player: usize, button: Inputs, held: bool) {
+        let index = Turbo::BUTTONS.iter().position(|&turbo| turbo == button).expect("Only A and B have turbo");
+        self.held[player][index] = held;
+        if !held {
+            nes.set_button_pressed_status(player, button, false);
+        }
+    }
+
+    // Call once per frame, before running it
+    pub fn apply(&mut self, nes: &mut Nes) {
+        if self.held.iter().flatten().all(|&held| !held) {
+            // Start the next press straight away
+            self.frame = 0;
+            return;
+        }
+
+        let pressed = (self.frame / self.frames).is_multiple_of(2);
+        for (player, held) in self.held.iter().enumerate() {
+            for (&button, _) in Turbo::BUTTONS.iter().zip(held).filter(|(_, &held)| held) {
+                nes.set_button_pressed_status(player, button, pressed);
+            }
+        }
+        self.frame += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::nestest;
+
+    #[test]
+    fn test_turbo() {
+        let mut nes = nestest();
+        let mut turbo = Turbo::new(2);
+        turbo.set_held(&mut nes, 1, Inputs::B, true);
+
+        let mut pressed = Vec::new();
+        for _ in 0..6 {
+            turbo.apply(&mut nes);
+            pressed.push(nes.buttons(1));
+        }
+        assert_eq!(pressed, [2, 2, 0, 0, 2, 2]);
+
+        turbo.set_held(&mut nes, 1, Inputs::B, false);
+        turbo.apply(&mut nes);
+        assert_eq!(nes.buttons(1), 0);
+    }
+}
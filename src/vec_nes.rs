@@ -0,0 +1,106 @@
+use rayon::prelude::*;
+
+use crate::cart::Cart;
+use crate::env::{EnvConfig, NesEnv};
+
+// N independent environments stepped in parallel on the rayon thread pool.
+// Results are written into buffers owned by VecNes, so stepping does not allocate.
+pub struct VecNes {
+    envs: Vec<NesEnv>,
+    observation_size: usize,
+    observations: Vec<u8>,
+    rewards: Vec<f64>,
+    dones: Vec<bool>,
+}
+
+impl VecNes {
+    // Each environment gets its own seed, counting up from the one in the config
+    pub fn new(cart: Cart, config: EnvConfig, num_envs: usize) -> Self {
+        assert!(num_envs > 0, "VecNes needs at least one environment");
+        let envs: Vec<NesEnv> = (0..num_envs)
+            .map(|i| {
+                let mut config = config.clone();
+                config.seed = config.seed.wrapping_add(i as u64);
+                NesEnv::new(cart.clone(), config)
+            })
+            .collect();
+        let observation_size = envs[0].observation_size();
+
+        VecNes {
+            envs,
+            observation_size,
+            observations: vec![0; observation_size * num_envs],
+            rewards: vec![0.0; num_envs],
+            dones: vec![false; num_envs],
+        }
+    }
+
+    pub fn num_envs(&self) -> usize {
+        self.envs.len()
+    }
+
+    pub fn observation_size(&self) -> usize {
+        self.observation_size
+    }
+
+    pub fn envs(&self) -> &[NesEnv] {
+        &self.envs
+    }
+
+    // Returns num_envs observations laid out one after the other
+    pub fn reset(&mut self) -> &[u8] {
+        self.envs.par_iter_mut()
+            .zip(self.observations.par_chunks_mut(self.observation_size))
+            .for_each(|(env, observation)| {
+                env.reset();
+                env.write_observation(observation);
+            });
+        &self.observations
+    }
+
+    // Takes one action per environment. Environments that finish are reset straight away,
+    // the observation returned for them is the first of the new episode.
+    pub fn step(&mut self, actions: &[u8]) -> (&[u8], &[f64], &[bool]) {
+        assert_eq!(actions.len(), self.envs.len(), "Expected one action per environment");
+
+        self.envs.par_iter_mut()
+            .zip(self.observations.par_chunks_mut(self.observation_size))
+            .zip(self.rewards.par_iter_mut())
+            .zip(self.dones.par_iter_mut())
+            .zip(actions.par_iter())
+            .for_each(|((((env, observation), reward), done), &action)| {
+                let (step_reward, step_done) = env.advance(action);
+                if step_done {
+                    env.reset();
+                }
+                env.write_observation(observation);
+                *reward = step_reward;
+                *done = step_done;
+            });
+
+        (&self.observations, &self.rewards, &self.dones)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::nestest_cart;
+
+    fn assert_send<T: Send>() {}
+
+    #[test]
+    fn test_step() {
+        assert_send::<NesEnv>();
+
+        let cart = nestest_cart();
+        let config = EnvConfig::parse("observation = \"ram\"").unwrap();
+        let mut envs = VecNes::new(cart, config, 4);
+
+        assert_eq!(envs.reset().len(), 4 * 2048);
+        let (observations, rewards, dones) = envs.step(&[0, 1, 2, 3]);
+        assert_eq!(observations.len(), 4 * 2048);
+        assert_eq!(rewards, [0.0; 4]);
+        assert_eq!(dones, [false; 4]);
+    }
+}
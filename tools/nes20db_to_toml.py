@@ -0,0 +1,63 @@
+#!/usr/bin/env python3
+"""
+Converts the NES 2.0 header database (nes20db.xml) into entries for src/game_db.toml.
+
+Each <game> in the database describes one dump: the CRC32 and SHA-1 of its PRG and CHR ROM together,
+and the header it should have. Append the output to src/game_db.toml:
+
+    python3 tools/nes20db_to_toml.py nes20db.xml >> src/game_db.toml
+"""
+import sys
+import xml.etree.ElementTree as ET
+from pathlib import PureWindowsPath
+
+MIRRORING = {"H": "Horizontal", "V": "Vertical", "4": "FourScreen"}
+# Multi-region games run as NTSC and Dendy as PAL, as when reading a NES 2.0 header
+REGIONS = {"0": "Ntsc", "1": "Pal", "2": "Ntsc", "3": "Pal"}
+
+
+def toml_string(value):
+    return '"' + value.replace("\\", "\\\\").replace('"', '\\"') + '"'
+
+
+def convert(path):
+    parser = ET.XMLParser(target=ET.TreeBuilder(insert_comments=True))
+    root = ET.parse(path, parser).getroot()
+    entries = []
+    for game in root.iter("game"):
+        rom = game.find("rom")
+        pcb = game.find("pcb")
+        if rom is None or pcb is None:
+            continue
+
+        # The dump's file name is in a comment at the top of each game
+        name = next((child.text.strip() for child in game if child.tag is ET.Comment), "")
+        name = PureWindowsPath(name).stem or rom.get("crc32")
+
+        lines = [
+            "[[game]]",
+            f"name = {toml_string(name)}",
+            f"crc32 = {toml_string(rom.get('crc32').upper())}",
+            f"sha1 = {toml_string(rom.get('sha1').upper())}",
+            f"mapper = {int(pcb.get('mapper'))}",
+            f"submapper = {int(pcb.get('submapper', '0'))}",
+        ]
+        mirroring = MIRRORING.get(pcb.get("mirroring"))
+        if mirroring:
+            lines.append(f"mirroring = {toml_string(mirroring)}")
+        lines.append(f"battery = {'true' if pcb.get('battery') == '1' else 'false'}")
+        console = game.find("console")
+        if console is not None and console.get("region") in REGIONS:
+            lines.append(f"region = {toml_string(REGIONS[console.get('region')])}")
+        expansion = game.find("expansion")
+        if expansion is not None:
+            lines.append(f"expansion_device = {int(expansion.get('type'))}")
+        entries.append("\n".join(lines))
+    return entries
+
+
+if __name__ == "__main__":
+    if len(sys.argv) != 2:
+        sys.exit(f"Usage: {sys.argv[0]} nes20db.xml")
+    for entry in convert(sys.argv[1]):
+        print(f"\n{entry}")